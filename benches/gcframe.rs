@@ -0,0 +1,36 @@
+//! Times `GCFrame` against the original `VecDeque`/`HashMap` implementation
+//! it replaced on the test genome; the unit tests in `src/gcframe.rs` check
+//! that both give the same frame plot.
+//!
+//! Run with `cargo bench --bench gcframe`.
+
+#[path = "../src/gcframe/reference.rs"]
+mod reference;
+
+use needletail::parse_fastx_file;
+use phannotate_r::gcframe::GCFrame;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+fn main() {
+    let mut reader = parse_fastx_file("test/MGYG000303750.fna").expect("Failed to open test genome");
+    let (mut old, mut new) = (Duration::ZERO, Duration::ZERO);
+    let mut bases = 0;
+    while let Some(record) = reader.next() {
+        let seq = record.expect("Failed to read record").seq().to_ascii_uppercase();
+        bases += seq.len();
+
+        let started = Instant::now();
+        black_box(reference::get(&seq, 120));
+        old += started.elapsed();
+
+        let started = Instant::now();
+        black_box(GCFrame::new(120).get(&seq));
+        new += started.elapsed();
+    }
+
+    println!("GC frame plot of {} bases", bases);
+    println!("  VecDeque/HashMap: {:>10.2?}", old);
+    println!("  byte window:      {:>10.2?}", new);
+    println!("  speedup:          {:>9.1}x", old.as_secs_f64() / new.as_secs_f64());
+}
@@ -0,0 +1,511 @@
+use crate::edges::Edge;
+use crate::node::Node;
+use crate::orfs::{Contig, Orf, Orfs};
+use crate::rbs::RbsModel;
+use crate::repeats::{find_terminal_repeat, TerminalRepeat};
+use crate::translate::{GeneticCode, SUPPORTED_TABLES};
+use crate::{graph, misc, path};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Tables tried by `Table::Auto`, the standard code first.
+pub const AUTO_TABLES: [u8; 3] = [11, 4, 15];
+/// Coding density an alternative code must add over table 11 to be chosen.
+pub const AUTO_MIN_DENSITY_GAIN: f64 = 0.05;
+/// Shortest contig `Table::Auto` picks a code for, as Prodigal requires of
+/// its training sequence; shorter ones keep table 11.
+pub const AUTO_MIN_LENGTH: usize = 20_000;
+
+/// Translation table to call genes with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    Fixed(u8),
+    /// Choose between the `AUTO_TABLES` per contig.
+    Auto,
+}
+
+impl Default for Table {
+    fn default() -> Self {
+        Table::Fixed(11)
+    }
+}
+
+/// Parse start codons and weights such as `atg:0.85,gtg:0.10,ttg:0.05`,
+/// normalised by the largest weight, which must be positive.
+pub fn parse_start_codons(raw: &str) -> Result<HashMap<String, f64>, String> {
+    let mut codons = HashMap::new();
+    for pair in raw.split(',') {
+        let (codon, weight) = pair
+            .split_once(':')
+            .ok_or_else(|| format!("`{}` is not a codon:weight pair", pair))?;
+        let weight = Decimal::from_str(weight).map_err(|_| format!("`{}` is not a weight", weight))?;
+        codons.insert(codon.to_uppercase(), weight);
+    }
+    normalise_start_weights(codons)
+}
+
+/// Start codon weights divided by the largest one, refusing negative weights
+/// and sets where none is positive.
+fn normalise_start_weights(codons: HashMap<String, Decimal>) -> Result<HashMap<String, f64>, String> {
+    if let Some((codon, _)) = codons.iter().find(|(_, weight)| weight.is_sign_negative()) {
+        return Err(format!("the weight of {} is negative", codon));
+    }
+    // Normalize by max
+    let max = codons.values().copied().max().unwrap_or_default();
+    if max.is_zero() {
+        return Err("no start codon has a positive weight".to_string());
+    }
+    Ok(codons
+        .into_iter()
+        .map(|(codon, weight)| (codon, (weight / max).to_f64().unwrap()))
+        .collect())
+}
+
+/// Parse stop codons such as `tag,tga,taa`.
+pub fn parse_stop_codons(raw: &str) -> Vec<String> {
+    raw.split(',').map(|s| s.to_uppercase()).collect()
+}
+
+/// A called gene, with coordinates as PHANOTATE writes them: 1-based,
+/// including the stop codon, and `start > stop` on the reverse strand. On a
+/// circular contig a gene may run across the origin, which flips that order.
+/// A partial gene runs off the end of a linear contig or into an assembly gap
+/// instead, and its coordinates stop at the last whole codon before it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gene {
+    pub start: usize,
+    pub stop: usize,
+    pub frame: isize,
+    pub score: f64,
+    pub rbs_motif: Option<String>,
+    pub rbs_spacer: Option<usize>,
+    pub rbs_score: f64,
+    /// the coding strand, stop codon included
+    pub seq: Vec<u8>,
+    pub translation: String,
+    /// the gene runs off the contig or into a gap before its start codon
+    pub partial_start: bool,
+    /// the gene runs off the contig or into a gap before its stop codon
+    pub partial_stop: bool,
+}
+
+impl Gene {
+    /// The gene of `orf`, with its coordinates on `contig` mapped back to the
+    /// underlying sequence.
+    pub fn from_orf(orf: &Orf, code: &GeneticCode, contig: &Contig) -> Self {
+        let (start, stop) = if orf.frame > 0 { (orf.start, orf.stop + 2) } else { (orf.start + 2, orf.stop) };
+        // the frame follows the ORF's leftmost codon on the underlying sequence
+        let left_codon = contig.original_position(orf.start.min(orf.stop));
+        let frame = ((left_codon - 1) % 3 + 1) as isize * orf.frame.signum();
+        Gene {
+            start: contig.original_position(start),
+            stop: contig.original_position(stop),
+            frame,
+            score: orf.weight,
+            rbs_motif: (!orf.rbs.is_empty()).then(|| orf.rbs.clone()),
+            rbs_spacer: orf.rbs_spacer,
+            rbs_score: orf.rbs_score,
+            seq: orf.seq.to_vec(),
+            translation: code.translate(&orf.seq, orf.has_start()),
+            partial_start: !orf.has_start(),
+            partial_stop: !orf.has_stop(),
+        }
+    }
+
+    pub fn is_forward(&self) -> bool {
+        self.frame > 0
+    }
+
+    /// Whether the gene runs across the origin of a circular contig.
+    pub fn wraps_origin(&self) -> bool {
+        self.is_forward() == (self.start > self.stop)
+    }
+
+    /// Leftmost base of the gene on the contig, the one before the origin
+    /// for genes that wrap around it.
+    pub fn left(&self) -> usize {
+        if self.is_forward() { self.start } else { self.stop }
+    }
+
+    /// Rightmost base of the gene on the contig, the one after the origin
+    /// for genes that wrap around it.
+    pub fn right(&self) -> usize {
+        if self.is_forward() { self.stop } else { self.start }
+    }
+
+    /// Whether the gene runs off the contig or into a gap on its left.
+    pub fn partial_left(&self) -> bool {
+        if self.is_forward() { self.partial_start } else { self.partial_stop }
+    }
+
+    /// Whether the gene runs off the contig or into a gap on its right.
+    pub fn partial_right(&self) -> bool {
+        if self.is_forward() { self.partial_stop } else { self.partial_start }
+    }
+}
+
+/// The middle of the longest stretch between genes on a circular contig of
+/// `length` bases, counting the one across the origin, given the `(left,
+/// right)` spans of its genes in order of `left`. Each stretch starts past
+/// the furthest gene end seen so far. With no genes at all, the middle of the
+/// contig, as the only gene may be one across the origin. 0-based, as
+/// `get_orfs` takes it.
+fn cut_point(spans: &[(usize, usize)], length: usize) -> usize {
+    let (Some(&(first, _)), Some(last)) = (spans.first(), spans.iter().map(|&(_, right)| right).max()) else {
+        return length / 2;
+    };
+    // the gap after each gene, as its first base and length
+    let mut best = (last + 1, (length - last) + (first - 1));
+    let mut reach = 0;
+    for pair in spans.windows(2) {
+        reach = reach.max(pair[0].1);
+        let gap = pair[1].0.saturating_sub(reach + 1);
+        if gap > best.1 {
+            best = (reach + 1, gap);
+        }
+    }
+    (best.0 - 1 + best.1 / 2) % length.max(1)
+}
+
+/// Start and stop codons used for one gene-calling run.
+struct Codons {
+    code: GeneticCode,
+    start_weights: HashMap<String, f64>,
+    stop_codons: Vec<String>,
+}
+
+/// Gene calls for one contig under one genetic code, with the graph they
+/// were found in.
+pub struct Annotation<'a> {
+    pub code: GeneticCode,
+    pub circular: bool,
+    pub repeat: Option<TerminalRepeat>,
+    pub orfs: Orfs<'a>,
+    pub edges: Vec<Edge<Node>>,
+    pub path: Vec<Node>,
+}
+
+impl Annotation<'_> {
+    /// The genes on the best path, in genome order.
+    pub fn genes(&self) -> Vec<Gene> {
+        let contig = self.orfs.contig();
+        let mut genes: Vec<Gene> = graph::get_genes(&self.path, &self.orfs)
+            .into_iter()
+            .map(|orf| Gene::from_orf(orf, &self.code, &contig))
+            .collect();
+        genes.sort_by_key(Gene::left);
+        genes
+    }
+
+    /// Length of the circular genome the genes wrap around, short of the
+    /// contig by a trimmed terminal repeat; `None` for linear contigs.
+    pub fn circular_length(&self) -> Option<usize> {
+        self.circular.then_some(self.orfs.contig_length)
+    }
+
+    /// Where to cut a circular contig so that no gene crosses the cut, see
+    /// `cut_point`.
+    fn circular_origin(&self) -> usize {
+        let spans: Vec<(usize, usize)> = self.genes().iter().map(|gene| (gene.left(), gene.right())).collect();
+        cut_point(&spans, self.orfs.contig_length)
+    }
+
+    /// Fraction of the contig covered by genes and their mean length.
+    pub fn coding_stats(&self) -> (f64, f64) {
+        let genes = graph::get_genes(&self.path, &self.orfs);
+        let mut covered = vec![false; self.orfs.contig_length + 1];
+        for orf in &genes {
+            let (left, right) = (orf.start.min(orf.stop), orf.start.max(orf.stop) + 2);
+            covered[left..=right].iter_mut().for_each(|c| *c = true);
+        }
+        let coding = covered.iter().filter(|&&c| c).count();
+        let density = coding as f64 / self.orfs.contig_length.max(1) as f64;
+        let mean = genes.iter().map(|orf| orf.length).sum::<usize>() as f64 / genes.len().max(1) as f64;
+        (density, mean)
+    }
+}
+
+/// Calls genes on in-memory sequences.
+///
+/// ```
+/// use phannotate_r::{Annotator, Table};
+///
+/// // one 303 bp gene: ATG, 99 GCT codons and TAA
+/// let dna = [b"ATG".as_slice(), &b"GCT".repeat(99), b"TAA"].concat();
+/// let genes = Annotator::new().min_length(120).table(Table::Auto)?.annotate(&dna);
+/// assert_eq!((genes[0].start, genes[0].stop), (1, 303));
+/// assert!(genes[0].translation.starts_with("MAAA"));
+/// # Ok::<(), String>(())
+/// ```
+pub struct Annotator {
+    start_codons: Option<HashMap<String, f64>>,
+    stop_codons: Option<Vec<String>>,
+    min_length: usize,
+    table: Table,
+    rbs_model: Option<RbsModel>,
+    rbs_denovo: bool,
+    circular: bool,
+    trim_repeats: bool,
+    partial: bool,
+}
+
+impl Default for Annotator {
+    fn default() -> Self {
+        Annotator {
+            start_codons: None,
+            stop_codons: None,
+            min_length: 90,
+            table: Table::default(),
+            rbs_model: None,
+            rbs_denovo: false,
+            circular: false,
+            trim_repeats: false,
+            partial: false,
+        }
+    }
+}
+
+impl Annotator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start codons and their weights, normalised by the largest weight
+    /// [default: derived from the table]. Fails like `parse_start_codons` on
+    /// weights that are negative, not finite, or none of them positive.
+    pub fn start_codons(mut self, weights: HashMap<String, f64>) -> Result<Self, String> {
+        let weights = weights
+            .into_iter()
+            .map(|(codon, weight)| {
+                let weight = Decimal::from_f64_retain(weight).ok_or_else(|| format!("`{}` is not a weight", weight))?;
+                Ok((codon.to_uppercase(), weight))
+            })
+            .collect::<Result<_, String>>()?;
+        self.start_codons = Some(normalise_start_weights(weights)?);
+        Ok(self)
+    }
+
+    /// Stop codons [default: derived from the table]. Ignored with
+    /// `Table::Auto`, where every table brings its own.
+    pub fn stop_codons(mut self, codons: Vec<String>) -> Self {
+        self.stop_codons = Some(codons.iter().map(|c| c.to_uppercase()).collect());
+        self
+    }
+
+    /// Shortest ORF considered, in bases including the stop codon.
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Fails with `Table::Fixed` of a table not in `SUPPORTED_TABLES`.
+    pub fn table(mut self, table: Table) -> Result<Self, String> {
+        if let Table::Fixed(id) = table {
+            GeneticCode::new(id)
+                .ok_or_else(|| format!("unsupported table {}, expected one of {:?}", id, SUPPORTED_TABLES))?;
+        }
+        self.table = table;
+        Ok(self)
+    }
+
+    /// Score RBSs with `model` instead of the built-in Shine-Dalgarno
+    /// classes.
+    pub fn rbs_model(mut self, model: RbsModel) -> Self {
+        self.rbs_model = Some(model);
+        self
+    }
+
+    /// Learn the RBS model of every contig from its own long ORFs, which
+    /// takes precedence over `rbs_model`.
+    pub fn rbs_denovo(mut self, denovo: bool) -> Self {
+        self.rbs_denovo = denovo;
+        self
+    }
+
+    /// Treat every contig as circular, see `annotate_circular`.
+    pub fn circular(mut self, circular: bool) -> Self {
+        self.circular = circular;
+        self
+    }
+
+    /// Drop the right copy of a direct terminal repeat before looking for
+    /// ORFs, so the genes it holds are not called twice.
+    pub fn trim_repeats(mut self, trim: bool) -> Self {
+        self.trim_repeats = trim;
+        self
+    }
+
+    /// Let genes run off either end of linear contigs, as they do on the
+    /// fragments of a metagenome assembly. Circular contigs have no ends.
+    /// Genes may run into assembly gaps either way.
+    pub fn partial(mut self, partial: bool) -> Self {
+        self.partial = partial;
+        self
+    }
+
+    fn codons(&self, code: GeneticCode, derive_stops: bool) -> Codons {
+        let start_weights = match &self.start_codons {
+            Some(weights) => weights.clone(),
+            None => parse_start_codons(&code.start_codons()).expect("Every table has weighted start codons"),
+        };
+        let stop_codons = match &self.stop_codons {
+            Some(codons) if !derive_stops => codons.clone(),
+            _ => code.stop_codons(),
+        };
+        Codons { code, start_weights, stop_codons }
+    }
+
+    /// Call the genes of one contig.
+    pub fn annotate(&self, dna: &[u8]) -> Vec<Gene> {
+        self.annotate_contig(dna).genes()
+    }
+
+    /// Call the genes of one contig, keeping the ORFs and gene graph behind
+    /// the calls. A direct terminal repeat is detected on the way and, with
+    /// `trim_repeats`, its right copy left out.
+    pub fn annotate_contig<'a>(&self, dna: &'a [u8]) -> Annotation<'a> {
+        self.annotate_topology(dna, self.circular)
+    }
+
+    /// Call the genes of a circular contig, see `annotate_contig`.
+    pub fn annotate_circular<'a>(&self, dna: &'a [u8]) -> Annotation<'a> {
+        self.annotate_topology(dna, true)
+    }
+
+    fn annotate_topology<'a>(&self, dna: &'a [u8], circular: bool) -> Annotation<'a> {
+        let repeat = find_terminal_repeat(dna);
+        let dna = match repeat {
+            Some(repeat) if self.trim_repeats => &dna[..dna.len() - repeat.length],
+            _ => dna,
+        };
+        let mut annotation = if circular { self.annotate_cut(dna) } else { self.annotate_linear(dna, self.partial) };
+        annotation.repeat = repeat;
+        annotation
+    }
+
+    fn annotate_linear<'a>(&self, dna: &'a [u8], partial: bool) -> Annotation<'a> {
+        match self.table {
+            Table::Fixed(id) => self.annotate_with(dna, &self.codons(GeneticCode::new(id).unwrap(), false), 0, partial),
+            Table::Auto => self.detect_table(dna, partial),
+        }
+    }
+
+    /// Call the genes of a circular contig. It is first annotated as linear,
+    /// then again cut at the `circular_origin` of those genes, so genes
+    /// running across the origin are found whole.
+    fn annotate_cut<'a>(&self, dna: &'a [u8]) -> Annotation<'a> {
+        let mut annotation = self.annotate_linear(dna, false);
+        let origin = annotation.circular_origin();
+        if origin != 0 {
+            let codons = self.codons(annotation.code, self.table == Table::Auto);
+            annotation = self.annotate_with(dna, &codons, origin, false);
+        }
+        annotation.circular = true;
+        annotation
+    }
+
+    fn annotate_with<'a>(&self, dna: &'a [u8], codons: &Codons, origin: usize, partial: bool) -> Annotation<'a> {
+        let starts: Vec<&str> = codons.start_weights.keys().map(String::as_str).collect();
+        let stops: Vec<&str> = codons.stop_codons.iter().map(String::as_str).collect();
+        let seed_model = self.rbs_model.as_ref().unwrap_or_else(|| RbsModel::default_model());
+        let mut orfs = misc::get_orfs(dna, Some(&starts), Some(&stops), self.min_length, seed_model, origin, partial);
+        let learned = self.rbs_denovo.then(|| misc::learn_rbs(&mut orfs));
+        let pstop = orfs.pstop();
+        misc::score_orfs(&mut orfs, &codons.start_weights, learned.as_ref().unwrap_or(seed_model), pstop);
+        let edges = graph::get_graph(&orfs, pstop);
+        let path = path::get_path(&edges, &graph::source(), &graph::target(orfs.contig_length))
+            .expect("No path through the gene graph");
+        Annotation { code: codons.code, circular: false, repeat: None, orfs, edges, path }
+    }
+
+    /// Pick the genetic code for a contig the way Prodigal does for recoded
+    /// phages: keep table 11 unless an alternative code covers clearly more
+    /// of the contig with longer genes. Contigs shorter than
+    /// `AUTO_MIN_LENGTH` hold too few genes to tell, and keep table 11.
+    fn detect_table<'a>(&self, dna: &'a [u8], partial: bool) -> Annotation<'a> {
+        let mut candidates = AUTO_TABLES.iter().map(|&id| self.codons(GeneticCode::new(id).unwrap(), true));
+        let mut best = self.annotate_with(dna, &candidates.next().unwrap(), 0, partial);
+        if dna.len() < AUTO_MIN_LENGTH {
+            return best;
+        }
+        let (standard_density, standard_mean) = best.coding_stats();
+        let mut best_density = standard_density;
+        for codons in candidates {
+            let annotation = self.annotate_with(dna, &codons, 0, partial);
+            let (density, mean) = annotation.coding_stats();
+            if density >= standard_density + AUTO_MIN_DENSITY_GAIN && mean > standard_mean && density > best_density {
+                best = annotation;
+                best_density = density;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_start_codons_normalises_weights() {
+        let weights = parse_start_codons("atg:0.8,GTG:0.2").unwrap();
+        assert_eq!(weights, HashMap::from([("ATG".to_string(), 1.0), ("GTG".to_string(), 0.25)]));
+    }
+
+    #[test]
+    fn parse_start_codons_rejects_bad_weights() {
+        assert!(parse_start_codons("atg:x").is_err());
+        assert!(parse_start_codons("atg").is_err());
+        assert!(parse_start_codons("atg:0,gtg:0").is_err());
+        assert!(parse_start_codons("atg:1,gtg:-0.5").is_err());
+    }
+
+    #[test]
+    fn start_codons_rejects_bad_weights() {
+        let weights = |pairs: &[(&str, f64)]| pairs.iter().map(|&(c, w)| (c.to_string(), w)).collect();
+        assert!(Annotator::new().start_codons(weights(&[])).is_err());
+        assert!(Annotator::new().start_codons(weights(&[("ATG", 0.0), ("GTG", 0.0)])).is_err());
+        assert!(Annotator::new().start_codons(weights(&[("ATG", 1.0), ("GTG", -0.5)])).is_err());
+        assert!(Annotator::new().start_codons(weights(&[("ATG", f64::NAN)])).is_err());
+        assert!(Annotator::new().start_codons(weights(&[("ATG", f64::INFINITY)])).is_err());
+
+        let annotator = Annotator::new().start_codons(weights(&[("atg", 0.8), ("GTG", 0.2)])).unwrap();
+        assert_eq!(annotator.start_codons, Some(weights(&[("ATG", 1.0), ("GTG", 0.25)])));
+    }
+
+    #[test]
+    fn table_rejects_unsupported_codes() {
+        assert!(Annotator::new().table(Table::Fixed(2)).is_err());
+        assert!(Annotator::new().table(Table::Fixed(4)).is_ok());
+    }
+
+    #[test]
+    fn cut_point_takes_the_middle_of_the_longest_gap() {
+        assert_eq!(cut_point(&[(101, 400), (1001, 1300)], 1500), 700);
+        // across the origin: 1301..2000 and 1..100
+        assert_eq!(cut_point(&[(101, 400), (501, 1300)], 2000), 1700);
+        assert_eq!(cut_point(&[], 2000), 1000);
+    }
+
+    #[test]
+    fn cut_point_skips_genes_nested_in_another() {
+        // the short gene inside 1001..3000 leaves no gap after itself
+        let spans = [(1, 800), (1001, 3000), (1501, 1600), (3101, 3900)];
+        assert_eq!(cut_point(&spans, 4000), 900);
+    }
+
+    #[test]
+    fn finds_a_lone_gene_across_the_origin() {
+        // ATG, 99 GCT codons and TAA cut after 150 bases, around a stretch
+        // with stops in all six frames
+        let gene = [b"ATG".as_slice(), &b"GCT".repeat(99), b"TAA"].concat();
+        let dna = [&gene[150..], &b"TTAG".repeat(100), &gene[..150]].concat();
+        assert!(Annotator::new().annotate(&dna).is_empty());
+        let genes = Annotator::new().annotate_circular(&dna).genes();
+        assert_eq!(genes.len(), 1);
+        assert_eq!((genes[0].start, genes[0].stop), (554, 153));
+        assert!(genes[0].wraps_origin());
+    }
+}
@@ -1,107 +1,115 @@
-use std::collections::{HashMap, VecDeque};
-
+/// GC frame plot: the G+C count of a sliding window over each of the three
+/// codon positions.
+///
+/// Every third base belongs to the same frame, and each frame keeps its own
+/// window of `window / 3` bases. The window for the next base is derived from
+/// the previous one by adding the entering base and dropping the leaving one,
+/// so a whole contig is processed in one pass without allocating anything
+/// but the result.
 pub struct GCFrame {
     window: usize,
-    states: Box<dyn Iterator<Item = usize>>,
-    bases: [Option<VecDeque<char>>; 4],
-    frequency: [Option<HashMap<char, usize>>; 4],
-    total: [VecDeque<usize>; 4],
-    freq: Vec<[usize; 3]>,
 }
 
-impl GCFrame {
-    pub fn new(window: usize) -> Self {
-        let window = window / 3;
-
-        let mut bases: [Option<VecDeque<char>>; 4] = Default::default();
-        let mut frequency: [Option<HashMap<char, usize>>; 4] = Default::default();
-
-        for frame in 1..=3 {
-            bases[frame] = Some(VecDeque::from(vec!['-'; window]));
-            let mut freq_map = HashMap::new();
-            for &base in ['A', 'T', 'C', 'G', '-'].iter() {
-                freq_map.insert(base, 0);
-            }
-            frequency[frame] = Some(freq_map);
-        }
+fn is_gc(base: u8) -> bool {
+    matches!(base, b'G' | b'C' | b'g' | b'c')
+}
 
-        let states = Box::new((1..=3).cycle());
+/// The running G+C count of one frame's window, followed by the counts
+/// produced while the window is drained at the end of the sequence.
+struct FrameTotals<'a> {
+    seq: &'a [u8],
+    frame: usize,
+    window: usize,
+    /// number of bases in this frame
+    len: usize,
+    /// index of the next base of this frame, then of the next drain step
+    next: usize,
+    gc: usize,
+}
 
-        GCFrame {
-            window,
-            states,
-            bases,
-            frequency,
-            total: Default::default(),
-            freq: Vec::new(),
-        }
+impl<'a> FrameTotals<'a> {
+    fn new(seq: &'a [u8], frame: usize, window: usize) -> Self {
+        let len = (seq.len() + 2 - frame) / 3;
+        FrameTotals { seq, frame, window, len, next: 0, gc: 0 }
     }
 
-    pub fn add_base(&mut self, base: char) {
-        let frame = self.states.next().unwrap();
-        if let (Some(bases_frame), Some(freq_map)) =
-            (&mut self.bases[frame], &mut self.frequency[frame])
-        {
-            bases_frame.push_back(base);
-            *freq_map.entry(base).or_insert(0) += 1;
-
-            let removed = bases_frame.pop_front().unwrap();
-            *freq_map.entry(removed).or_insert(0) -= 1;
+    /// The `k`th base of this frame, or `None` for the padding before it.
+    fn base(&self, k: isize) -> Option<u8> {
+        (k >= 0).then(|| self.seq[self.frame + 3 * k as usize])
+    }
 
-            let gc_count = freq_map.get(&'G').unwrap_or(&0) + freq_map.get(&'C').unwrap_or(&0);
-            self.total[frame].push_back(gc_count);
-        }
+    fn total_len(&self) -> usize {
+        self.len + self.window / 2
     }
+}
+
+impl Iterator for FrameTotals<'_> {
+    type Item = usize;
 
-    fn close(&mut self) {
-        for _ in 0..(self.window / 2) {
-            for frame in 1..=3 {
-                if let (Some(bases_frame), Some(freq_map)) =
-                    (&mut self.bases[frame], &mut self.frequency[frame])
-                {
-                    bases_frame.pop_front();
-                    let removed = bases_frame.pop_front().unwrap_or('-');
-                    *freq_map.entry(removed).or_insert(0) -= 1;
-                    let gc_count = freq_map.get(&'G').unwrap_or(&0) + freq_map.get(&'C').unwrap_or(&0);
-                    self.total[frame].push_back(gc_count);
+    fn next(&mut self) -> Option<usize> {
+        let k = self.next;
+        if k < self.len {
+            if self.window > 0 {
+                self.gc += is_gc(self.seq[self.frame + 3 * k]) as usize;
+                if let Some(leaving) = self.base(k as isize - self.window as isize) {
+                    self.gc -= is_gc(leaving) as usize;
                 }
             }
+        } else if k < self.total_len() {
+            // the window drains two bases per step but only the second one
+            // is taken off the count, as PHANOTATE does
+            let step = (k - self.len) as isize;
+            let first = self.len as isize - self.window as isize;
+            if let Some(leaving) = self.base(first + 2 * step + 1) {
+                self.gc -= is_gc(leaving) as usize;
+            }
+        } else {
+            return None;
         }
+        self.next += 1;
+        Some(self.gc)
     }
+}
 
-    pub fn get(&mut self) -> &Vec<[usize; 3]> {
-        self.close();
-        self.freq.push([20, 20, 20]);
-
-        let len = self.total[3].len().saturating_sub(1);
-        for i in 0..len {
-            let t1 = self.total[1][i];
-            let t2 = self.total[2][i];
-            let t3 = self.total[3][i];
-            let t1n = self.total[1].get(i + 1).copied().unwrap_or(0);
-            let t2n = self.total[2].get(i + 1).copied().unwrap_or(0);
-
-            self.freq.push([t1, t2, t3]);
-            self.freq.push([t2, t3, t1n]);
-            self.freq.push([t3, t1n, t2n]);
-        }
-
-        if let Some(i) = self.total[3].len().checked_sub(1) {
-            let t1 = self.total[1][i];
-            let t2 = self.total[2][i];
-            let t3 = self.total[3][i];
-            self.freq.push([t1, t2, t3]);
+impl GCFrame {
+    pub fn new(window: usize) -> Self {
+        GCFrame { window: window / 3 }
+    }
 
-            if let Some(t1n) = self.total[1].get(i + 1) {
-                self.freq.push([t2, t3, *t1n]);
-            }
+    /// The frame plot of `seq`, starting with a `[20, 20, 20]` placeholder.
+    /// Entry `i` holds the window counts of the frame of base `i` followed by
+    /// those of the next two frames.
+    pub fn get(&self, seq: &[u8]) -> Vec<[usize; 3]> {
+        let mut frames = [0, 1, 2].map(|frame| FrameTotals::new(seq, frame, self.window));
+        let lens = frames.each_ref().map(FrameTotals::total_len);
+        let mut freq = Vec::with_capacity(1 + lens.iter().sum::<usize>());
+        freq.push([20, 20, 20]);
+        if lens[2] == 0 {
+            return freq;
+        }
 
-            if let Some(t2n) = self.total[2].get(i + 1) {
-                self.freq.push([t3, self.total[1].get(i + 1).copied().unwrap_or(0), *t2n]);
+        let [first, second, third] = &mut frames;
+        let (mut t1, mut t2) = (first.next().unwrap(), second.next().unwrap());
+        for i in 0..lens[2] {
+            let t3 = third.next().unwrap();
+            let (t1n, t2n) = (first.next(), second.next());
+            freq.push([t1, t2, t3]);
+            if i + 1 < lens[2] {
+                freq.push([t2, t3, t1n.unwrap()]);
+                freq.push([t3, t1n.unwrap(), t2n.unwrap()]);
+            } else {
+                if let Some(t1n) = t1n {
+                    freq.push([t2, t3, t1n]);
+                }
+                if let Some(t2n) = t2n {
+                    freq.push([t3, t1n.unwrap_or(0), t2n]);
+                }
             }
+            t1 = t1n.unwrap_or(0);
+            t2 = t2n.unwrap_or(0);
         }
 
-        &self.freq
+        freq
     }
 }
 
@@ -136,3 +144,32 @@ pub fn min_idx(a: usize, b: usize, c: usize) -> usize {
         }
     }
 }
+
+#[cfg(test)]
+#[path = "gcframe/reference.rs"]
+mod reference;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::random_seq;
+
+    #[test]
+    fn matches_the_original_frame_plot_on_short_sequences() {
+        for window in [0, 3, 5, 120, 121, 122] {
+            for len in 0..50 {
+                let seq = random_seq(len, (window * 100 + len) as u64, b"ACGT");
+                assert_eq!(GCFrame::new(window).get(&seq), reference::get(&seq, window), "window {} length {}", window, len);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_the_original_frame_plot_on_the_test_genome() {
+        let mut reader = needletail::parse_fastx_file("test/MGYG000303750.fna").expect("Failed to open test genome");
+        while let Some(record) = reader.next() {
+            let seq = record.expect("Failed to read record").seq().to_ascii_uppercase();
+            assert_eq!(GCFrame::new(120).get(&seq), reference::get(&seq, 120));
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::edges::Edge;
+use crate::misc::{score_gap, score_overlap};
+use crate::node::Node;
+use crate::orfs::{Orf, Orfs};
+use std::collections::HashMap;
+
+/// Longest intergenic gap bridged by a direct edge between two genes, unless
+/// no gene starts within it at all.
+pub const MAX_GAP: i64 = 300;
+/// Longest overlap allowed between two consecutive genes.
+pub const MAX_OVERLAP: i64 = 300;
+
+pub fn source() -> Node {
+    Node::new("source", "source", 0, 0)
+}
+
+pub fn target(contig_length: usize) -> Node {
+    Node::new("target", "target", 0, contig_length as i32 + 1)
+}
+
+/// A node where a gene is entered (forward start / reverse stop), with the
+/// closest position at which any gene through it can be left again.
+struct Entry {
+    node: Node,
+    forward: bool,
+    min_exit: i64,
+}
+
+/// A node where a gene is left (forward stop / reverse start), with the
+/// furthest position at which any gene through it was entered, PHANOTATE's
+/// other end of the longest of them.
+struct Exit {
+    node: Node,
+    forward: bool,
+    other_end: i64,
+}
+
+/// Build the PHANOTATE gene graph.
+///
+/// Every ORF becomes a gene edge weighted by `Orf::weight`, walked in reading
+/// direction along the genome: `start -> stop` for forward ORFs and
+/// `stop -> start` for reverse ones. Consecutive genes are linked from the
+/// exit of one to the entry of the next by a gap edge (`score_gap`) or, when
+/// they share bases, an overlap edge (`score_overlap`). Overlaps are only
+/// allowed when every gene through the entry ends past the previous exit, so
+/// exits strictly increase along any path and the graph stays acyclic, and
+/// only when the previous gene was entered before the next one, so no gene is
+/// called inside another. `pstop` is the chance of a codon being a stop, see
+/// `Orfs::pstop`.
+pub fn get_graph(orfs: &Orfs, pstop: f64) -> Vec<Edge<Node>> {
+    let mut edges = Vec::new();
+    let mut entries: HashMap<Node, Entry> = HashMap::new();
+    let mut exits: HashMap<Node, Exit> = HashMap::new();
+
+    for orf in orfs.iter_orfs() {
+        let frame = orf.frame as i32;
+        let start = Node::new("CDS", "start", frame, orf.start as i32);
+        let stop = Node::new("CDS", "stop", frame, orf.stop as i32);
+        let (entry, exit) = if orf.frame > 0 { (start, stop) } else { (stop, start) };
+
+        let (entry_position, exit_position) = (entry.position as i64, exit.position as i64);
+        entries
+            .entry(entry.clone())
+            .and_modify(|e| e.min_exit = e.min_exit.min(exit_position))
+            .or_insert(Entry { node: entry.clone(), forward: orf.frame > 0, min_exit: exit_position });
+        exits
+            .entry(exit.clone())
+            .and_modify(|e| e.other_end = e.other_end.min(entry_position))
+            .or_insert(Exit { node: exit.clone(), forward: orf.frame > 0, other_end: entry_position });
+        edges.push(Edge::new(entry, exit, orf.weight));
+    }
+
+    let mut entries: Vec<Entry> = entries.into_values().collect();
+    entries.sort_by(|a, b| a.node.cmp(&b.node));
+    let mut exits: Vec<Exit> = exits.into_values().collect();
+    exits.sort_by(|a, b| a.node.cmp(&b.node));
+
+    // the contig ends are scored as gaps to genes lying just outside it
+    let contig_length = orfs.contig_length as i64;
+    let source = source();
+    let target = target(orfs.contig_length);
+    for entry in &entries {
+        let gap = entry.node.position - 1;
+        edges.push(Edge::new(source.clone(), entry.node.clone(), score_gap(gap, true, pstop)));
+    }
+    for exit in &exits {
+        let gap = (contig_length - exit.node.position as i64 - 2) as i32;
+        edges.push(Edge::new(exit.node.clone(), target.clone(), score_gap(gap, true, pstop)));
+    }
+    if entries.is_empty() {
+        edges.push(Edge::new(source, target, score_gap(contig_length as i32, true, pstop)));
+    }
+
+    for exit in &exits {
+        let l = exit.node.position as i64;
+        let first = entries.partition_point(|e| (e.node.position as i64) < l + 3 - MAX_OVERLAP);
+        let mut last = entries.partition_point(|e| (e.node.position as i64) <= l + MAX_GAP);
+        // with no gene starting within reach, bridge the gap to the next
+        // cluster of entries so the path can always move on
+        let gap_start = entries.partition_point(|e| (e.node.position as i64) < l + 3);
+        if gap_start == last && last < entries.len() {
+            let r0 = entries[last].node.position as i64;
+            last = entries.partition_point(|e| (e.node.position as i64) <= r0 + MAX_GAP);
+        }
+        for entry in &entries[first..last] {
+            let r = entry.node.position as i64;
+            let same = exit.forward == entry.forward;
+            let weight = if r >= l + 3 {
+                score_gap((r - l - 3) as i32, same, pstop)
+            } else if entry.min_exit > l && exit.other_end < r {
+                score_overlap(l + 3 - r, same, pstop)
+            } else {
+                continue;
+            };
+            edges.push(Edge::new(exit.node.clone(), entry.node.clone(), weight));
+        }
+    }
+
+    edges
+}
+
+/// The ORFs whose gene edges make up `path`, in genome order.
+pub fn get_genes<'o, 'a>(path: &[Node], orfs: &'o Orfs<'a>) -> Vec<&'o Orf<'a>> {
+    let mut genes: Vec<&Orf> = path
+        .windows(2)
+        .filter_map(|pair| {
+            let (left, right) = (&pair[0], &pair[1]);
+            if left.gene != "CDS" || right.gene != "CDS" || left.frame != right.frame {
+                return None;
+            }
+            match (left.node_type.as_str(), right.node_type.as_str(), left.frame > 0) {
+                ("start", "stop", true) => orfs.get_orf(left.position as usize, right.position as usize),
+                ("stop", "start", false) => orfs.get_orf(right.position as usize, left.position as usize),
+                _ => None,
+            }
+        })
+        .collect();
+    genes.sort_by_key(|orf| orf.start.min(orf.stop));
+    genes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    const PSTOP: f64 = 0.05;
+
+    /// ORFs at `(start, stop)` on a 2000 bp contig, reverse ones with
+    /// `stop < start`.
+    fn orfs(coordinates: &[(usize, usize)]) -> Orfs<'static> {
+        let mut orfs = Orfs::new(90, vec!["ATG".to_string()], vec!["TAA".to_string()]);
+        orfs.contig_length = 2000;
+        for &(start, stop) in coordinates {
+            let frame = ((start.min(stop) - 1) % 3 + 1) as isize * if start < stop { 1 } else { -1 };
+            let length = start.abs_diff(stop) + 3;
+            orfs.add_orf(start, stop, length, frame, Cow::Borrowed(b"ATGTAA"), String::new(), 0.0);
+        }
+        orfs
+    }
+
+    /// The node where the ORF at `(start, stop)` is left or entered.
+    fn node(start: usize, stop: usize, exit: bool) -> Node {
+        let forward = start < stop;
+        let frame = ((start.min(stop) - 1) % 3 + 1) as i32 * if forward { 1 } else { -1 };
+        let (node_type, position) = if exit == forward { ("stop", stop) } else { ("start", start) };
+        Node::new("CDS", node_type, frame, position as i32)
+    }
+
+    fn weight(edges: &[Edge<Node>], source: &Node, target: &Node) -> Option<f64> {
+        edges.iter().find(|e| &e.source == source && &e.target == target).map(|e| e.weight.0)
+    }
+
+    #[test]
+    fn links_genes_across_gaps() {
+        let orfs = orfs(&[(1, 298), (700, 401)]);
+        let edges = get_graph(&orfs, PSTOP);
+        // 301..400 lies between the two, and the second is on the other strand
+        let gap = weight(&edges, &node(1, 298, true), &node(700, 401, false));
+        assert_eq!(gap, Some(score_gap(100, false, PSTOP)));
+        assert_eq!(weight(&edges, &node(700, 401, true), &node(1, 298, false)), None);
+        assert_eq!(weight(&edges, &source(), &node(1, 298, false)), Some(score_gap(0, true, PSTOP)));
+        assert_eq!(weight(&edges, &node(700, 401, true), &target(2000)), Some(score_gap(1298, true, PSTOP)));
+    }
+
+    #[test]
+    fn links_overlapping_genes() {
+        let orfs = orfs(&[(1, 298), (290, 599)]);
+        let edges = get_graph(&orfs, PSTOP);
+        // the second starts on 290, 11 bases before the end of the first
+        let overlap = weight(&edges, &node(1, 298, true), &node(290, 599, false));
+        assert_eq!(overlap, Some(score_overlap(11, true, PSTOP)));
+    }
+
+    #[test]
+    fn never_enters_a_gene_before_the_previous_one() {
+        // the reverse gene 350..455 lies inside the forward one 265..1675
+        let edges = get_graph(&orfs(&[(455, 350), (265, 1675)]), PSTOP);
+        assert_eq!(weight(&edges, &node(455, 350, true), &node(265, 1675, false)), None);
+
+        // but a longer gene through the same stop may be followed by it
+        let edges = get_graph(&orfs(&[(352, 451), (262, 451), (265, 1675)]), PSTOP);
+        assert!(weight(&edges, &node(262, 451, true), &node(265, 1675, false)).is_some());
+    }
+}
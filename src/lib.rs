@@ -0,0 +1,24 @@
+//! PHANOTATE gene calling for phage genomes.
+//!
+//! `Annotator` runs the whole pipeline on a sequence held in memory; the
+//! modules below expose its stages for callers that need the ORFs or the
+//! gene graph themselves.
+
+pub mod annotator;
+pub mod edges;
+pub mod gcframe;
+pub mod graph;
+pub mod misc;
+pub mod node;
+pub mod orfs;
+pub mod output;
+pub mod path;
+pub mod rbs;
+pub mod repeats;
+pub mod seq;
+pub mod translate;
+
+#[cfg(test)]
+mod testutil;
+
+pub use annotator::{Annotation, Annotator, Gene, Table};
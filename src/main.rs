@@ -1,16 +1,14 @@
-mod edges;
-mod node;
-pub mod orfs;
-pub mod gcframe;
-pub mod seq;
-mod misc;
-use needletail::parse_fastx_file;
+use phannotate_r::annotator::{parse_start_codons, parse_stop_codons, AUTO_MIN_LENGTH};
+use phannotate_r::rbs::{MotifTable, RbsModel};
+use phannotate_r::{output, seq, translate, Annotator, Table};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use needletail::{parse_fastx_file, parse_fastx_stdin};
+use rayon::prelude::*;
 use clap::{Parser, ValueEnum};
-use std::fs::File;
-use std::io::{self, Write};
-use std::str::FromStr;
 use std::collections::HashMap;
-use rust_decimal::Decimal;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 
 /// Valid output formats
 #[derive(Debug, Clone, ValueEnum)]
@@ -18,6 +16,8 @@ enum OutputFormat {
     Tabular,
     Genbank,
     Fasta,
+    Gff3,
+    Faa,
 }
 
 /// Argument parser struct
@@ -26,10 +26,10 @@ enum OutputFormat {
           about = "PHANOTATE: A phage genome annotator",
           author = "Katelyn McNair")]
 struct Args {
-    /// Input FASTA file
+    /// Input FASTA file, plain or gzip, bzip2, xz or zstd compressed; `-` reads stdin
     infile: String,
 
-    /// Output file [default: stdout]
+    /// Output file, gzip compressed when it ends in `.gz` [default: stdout]
     #[arg(short, long, default_value = "-", value_parser = clap::value_parser!(String))]
     outfile: String,
 
@@ -37,70 +37,268 @@ struct Args {
     #[arg(short = 'f', long, default_value = "tabular")]
     format: OutputFormat,
 
-    /// Start codons and weights (e.g., "atg:0.85,gtg:0.10,ttg:0.05")
-    #[arg(short = 's', long, default_value = "ATG:0.85,GTG:0.10,TTG:0.05")]
-    start_codons: String,
+    /// Start codons and weights (e.g., "atg:0.85,gtg:0.10,ttg:0.05") [default: derived from --table]
+    #[arg(short = 's', long, value_parser = parse_start_codons)]
+    start_codons: Option<HashMap<String, f64>>,
 
-    /// Stop codons (e.g., "tag,tga,taa")
-    #[arg(short = 'e', long, default_value = "TAG,TGA,TAA")]
-    stop_codons: String,
+    /// Stop codons (e.g., "tag,tga,taa") [default: derived from --table]
+    #[arg(short = 'e', long)]
+    stop_codons: Option<String>,
 
     /// Minimum ORF length
     #[arg(short = 'l', long, default_value_t = 90)]
     minlen: usize,
 
+    /// NCBI translation table giving the default start/stop codons and used for protein sequences
+    /// (1, 4, 11, 15 or 25), or `auto` to choose between 11, 4 and 15 per contig of at least
+    /// 20 kb
+    #[arg(short = 'g', long, default_value = "11", value_parser = parse_table)]
+    table: Table,
+
+    /// Shine-Dalgarno motif table to score RBSs with, laid out as score, motifs and
+    /// spacer ranges per line [default: the built-in PHANOTATE classes]
+    #[arg(long, value_name = "FILE")]
+    rbs_motifs: Option<String>,
+
+    /// PWM (A, C, G and T counts per position) or k-mer table (k-mer and log-odds per line)
+    /// to score RBSs with instead of the Shine-Dalgarno classes
+    #[arg(long, value_name = "FILE", conflicts_with = "rbs_motifs")]
+    rbs_model: Option<String>,
+
+    /// Learn a k-mer RBS model from the long ORFs of each contig instead of using
+    /// Shine-Dalgarno classes, for hosts such as Bacteroidetes that lack them
+    #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with_all = ["rbs_motifs", "rbs_model"])]
+    rbs_denovo: bool,
+
+    /// Treat every contig as circular, letting genes run across the origin [default: only
+    /// records tagged `circular` in their FASTA header]
+    #[arg(short = 'c', long, action = clap::ArgAction::SetTrue)]
+    circular: bool,
+
+    /// Leave the right copy of a direct terminal repeat out of gene calling, so its genes
+    /// are not called twice, and out of circular records; repeats are reported either way
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    trim_repeats: bool,
+
+    /// Let genes run off either end of linear contigs, for fragmented assemblies such as
+    /// metagenomes; they are flagged `partial` in the output
+    #[arg(short = 'p', long, action = clap::ArgAction::SetTrue)]
+    partial: bool,
+
+    /// Number of contigs annotated at once; 0 uses every core
+    #[arg(short = 't', long, default_value_t = 1)]
+    threads: usize,
+
     /// Dump intermediate results
     #[arg(short = 'd', long, action = clap::ArgAction::SetTrue)]
     dump: bool,
 }
 
-fn parse_start_codons(raw: &str) -> HashMap<String, Decimal> {
-    let mut codons = HashMap::new();
-    for pair in raw.split(',') {
-        let parts: Vec<&str> = pair.split(':').collect();
-        if parts.len() == 2 {
-            let codon = parts[0].to_uppercase();
-            let weight = Decimal::from_str(parts[1]).unwrap();
-            codons.insert(codon, weight);
+/// Where the annotation is written.
+enum Output {
+    Plain(BufWriter<Box<dyn Write>>),
+    Gzip(BufWriter<GzEncoder<File>>),
+}
+
+impl Output {
+    fn create(path: &str) -> io::Result<Self> {
+        if path == "-" {
+            return Ok(Output::Plain(BufWriter::new(Box::new(io::stdout()))));
+        }
+        let file = File::create(path)?;
+        if path.ends_with(".gz") {
+            Ok(Output::Gzip(BufWriter::new(GzEncoder::new(file, Compression::default()))))
+        } else {
+            Ok(Output::Plain(BufWriter::new(Box::new(file))))
         }
     }
-    // Normalize by max
-    if let Some(max) = codons.values().cloned().max() {
-        for v in codons.values_mut() {
-            *v /= max;
+
+    /// Flush everything and, for gzip, write the trailer.
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Plain(mut writer) => writer.flush(),
+            Output::Gzip(writer) => writer.into_inner().map_err(|e| e.into_error())?.finish().map(drop),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Plain(writer) => writer.write(buf),
+            Output::Gzip(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Plain(writer) => writer.flush(),
+            Output::Gzip(writer) => writer.flush(),
         }
     }
-    codons
 }
 
-fn parse_stop_codons(raw: &str) -> Vec<String> {
-    raw.split(',').map(|s| s.to_uppercase()).collect()
+/// Whether a FASTA header marks its record as circular, e.g. with
+/// `[topology=circular]`, `circular=true` or a plain `circular` word.
+fn is_circular_header(header: &str) -> bool {
+    header
+        .split(|c: char| c.is_whitespace() || matches!(c, '[' | ']' | ',' | ';'))
+        .skip(1)
+        .any(|tag| {
+            let tag = tag.to_ascii_lowercase();
+            matches!(tag.as_str(), "circular" | "topology=circular" | "circular=true" | "is_circular=true")
+        })
+}
+
+fn parse_table(raw: &str) -> Result<Table, String> {
+    if raw.eq_ignore_ascii_case("auto") {
+        return Ok(Table::Auto);
+    }
+    let table: u8 = raw.parse().map_err(|_| format!("`{}` is not a table number", raw))?;
+    if translate::SUPPORTED_TABLES.contains(&table) {
+        Ok(Table::Fixed(table))
+    } else {
+        Err(format!("unsupported table, expected one of {:?}", translate::SUPPORTED_TABLES))
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
     // Open output file or default to stdout
-    let mut writer: Box<dyn Write> = if args.outfile == "-" {
-        Box::new(io::stdout())
+    let mut writer = Output::create(&args.outfile).expect("Unable to create output file");
+
+    let mut annotator = Annotator::new()
+        .min_length(args.minlen)
+        .table(args.table)
+        .expect("parse_table only accepts supported tables")
+        .rbs_denovo(args.rbs_denovo)
+        .circular(args.circular)
+        .trim_repeats(args.trim_repeats)
+        .partial(args.partial);
+    if let Some(weights) = &args.start_codons {
+        annotator = annotator.start_codons(weights.clone()).expect("parse_start_codons only accepts valid weights");
+    }
+    if let Some(raw) = &args.stop_codons {
+        annotator = annotator.stop_codons(parse_stop_codons(raw));
+    }
+    if let Some(path) = &args.rbs_motifs {
+        annotator = annotator.rbs_model(RbsModel::Motifs(MotifTable::from_file(path).expect("Failed to read RBS motif table")));
+    }
+    if let Some(path) = &args.rbs_model {
+        annotator = annotator.rbs_model(RbsModel::from_file(path).expect("Failed to read RBS model"));
+    }
+
+    if matches!(args.format, OutputFormat::Gff3) {
+        output::write_gff3_header(&mut writer).expect("Failed to write output");
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build()
+        .expect("Failed to start thread pool");
+
+    // contigs are read one batch per thread count at a time and written out
+    // before the next batch is read, so memory is bounded by the largest
+    // contigs rather than the whole file; output stays in input order, so
+    // every thread count writes the same file
+    let batch_size = pool.current_num_threads();
+    let mut batch: Vec<(String, bool, Vec<u8>)> = Vec::with_capacity(batch_size);
+    let mut reader = if args.infile == "-" {
+        parse_fastx_stdin()
     } else {
-        Box::new(File::create(&args.outfile).expect("Unable to create output file"))
-    };
-
-    let start_codons = parse_start_codons(&args.start_codons);
-    let stop_codons = parse_stop_codons(&args.stop_codons);
-
-    // Example debug print
-    writeln!(writer, "Parsed args:\n{:?}", args).unwrap();
-    writeln!(writer, "Start codons: {:?}", start_codons).unwrap();
-    writeln!(writer, "Stop codons: {:?}", stop_codons).unwrap();
-    // let mut reader = parse_fastx_file(&args.infile).expect("Failed to open FASTA file");
-    
-    // while let Some(record) = reader.next() {
-    //     let seqrec = record.expect("Failed to read record");
-    //     println!(">{}_{}", String::from_utf8_lossy(seqrec.id()), seqrec.seq().len());
-    //     println!("{:?}", seqrec.seq());
-    // }
-    let overlap_score = misc::score_gap(1203, false, 0.4);
-    println!("Overlap score: {}", overlap_score);
+        parse_fastx_file(&args.infile)
+    }
+    .expect("Failed to open FASTA file");
+    let mut exhausted = false;
+    while !exhausted {
+        batch.clear();
+        while batch.len() < batch_size {
+            let Some(record) = reader.next() else {
+                exhausted = true;
+                break;
+            };
+            let seqrec = record.expect("Failed to read record");
+            let id = String::from_utf8_lossy(seqrec.id());
+            let contig = id.split_whitespace().next().unwrap_or_default().to_string();
+            batch.push((contig, is_circular_header(&id), seqrec.seq().into_owned()));
+        }
+
+        let reports: Vec<(Vec<u8>, Vec<String>)> = pool.install(|| {
+            batch
+                .par_iter()
+                .map(|(contig, circular, dna)| annotate_record(&annotator, &args, contig, *circular, dna))
+                .collect()
+        });
+        for (report, log) in reports {
+            for line in log {
+                eprintln!("{}", line);
+            }
+            writer.write_all(&report).expect("Failed to write output");
+        }
+    }
+    writer.finish().expect("Failed to write output");
+}
+
+/// Annotate one contig and render its output, along with what to log about
+/// it: bases that are not plain A, C, G or T, and the table chosen for it
+/// under `--table auto`.
+fn annotate_record(annotator: &Annotator, args: &Args, contig: &str, circular: bool, dna: &[u8]) -> (Vec<u8>, Vec<String>) {
+    let mut log = Vec::new();
+    let bases = seq::base_report(dna);
+    if bases.ambiguous > 0 || bases.soft_masked > 0 {
+        log.push(format!(
+            "{}: {} ambiguous bases ({} in gaps breaking ORFs), {} soft-masked bases",
+            contig, bases.ambiguous, bases.gaps, bases.soft_masked
+        ));
+    }
+
+    let annotation = if circular { annotator.annotate_circular(dna) } else { annotator.annotate_contig(dna) };
+    if args.table == Table::Auto && annotation.orfs.contig_length < AUTO_MIN_LENGTH {
+        log.push(format!(
+            "{}: shorter than {} bp, keeping translation table {}",
+            contig, AUTO_MIN_LENGTH, annotation.code.id
+        ));
+    } else if args.table == Table::Auto {
+        let (density, mean) = annotation.coding_stats();
+        log.push(format!(
+            "{}: using translation table {} (coding density {:.3}, mean gene length {:.0})",
+            contig, annotation.code.id, density, mean
+        ));
+    }
+
+    let mut report = Vec::new();
+    if args.dump {
+        for edge in &annotation.edges {
+            writeln!(report, "{}", edge).expect("Failed to write output");
+        }
+        return (report, log);
+    }
+
+    let code = &annotation.code;
+    let genes = annotation.genes();
+    match args.format {
+        OutputFormat::Tabular => output::write_tabular(&mut report, contig, &genes),
+        OutputFormat::Genbank => output::write_genbank(
+            &mut report,
+            contig,
+            dna,
+            annotation.circular_length(),
+            annotation.repeat.as_ref(),
+            &genes,
+            code,
+        ),
+        OutputFormat::Fasta => output::write_fasta(&mut report, contig, &genes),
+        OutputFormat::Gff3 => output::write_gff3(
+            &mut report,
+            contig,
+            dna.len(),
+            annotation.circular_length(),
+            annotation.repeat.as_ref(),
+            &genes,
+        ),
+        OutputFormat::Faa => output::write_faa(&mut report, contig, &genes),
+    }
+    .expect("Failed to write output");
+    (report, log)
 }
@@ -1,15 +1,25 @@
-use crate::orfs::Orf;
-use crate::orfs::Orfs;
-use crate::gcframe::GCFrame;
+use crate::orfs::{Contig, Orf, Orfs};
+use crate::rbs::{KmerTable, RbsModel};
+use crate::gcframe::{max_idx, min_idx, GCFrame};
 use crate::seq;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
+/// Shortest ORF whose upstream region trains the RBS model.
+pub const RBS_TRAINING_MIN_LEN: usize = 600;
+
+/// Length of the k-mers of an RBS model learned de novo.
+pub const RBS_DENOVO_K: usize = 4;
+
+/// Width in bases of the GC frame plot window.
+pub const GC_WINDOW: usize = 120;
+
 pub fn score_overlap(len: i64, direction: bool, pstop: f64) -> f64 {
     // same direction - True else False
     let o: f64 = 1.0 - pstop;
     let s: f64 = 0.05;
     let mut score: f64 = 1.0 / (o * len as f64);
-    if direction == false {
+    if !direction {
         score += 1.0 / s;
     }
     score
@@ -25,131 +35,388 @@ pub fn score_gap(length: i32, direction: bool, pgap: f64) -> f64 {
 
     let mut score = 1.0 / g.powf((length as f64) / 3.0);
 
-    if direction == false {
+    if !direction {
         score += 1.0 / s;
     }
 
     score
 }
 
-fn average(vec: &Vec<f64>) -> Option<f64> {
-    if vec.is_empty() {
-        None
+/// Score the region upstream of a start codon against the built-in
+/// Shine-Dalgarno classes, 0 when none matches.
+pub fn score_rbs(seq: &str) -> u32 {
+    RbsModel::default_model().score(seq.as_bytes())
+}
+
+/// The 21 bases upstream of the start codon at `start`, on the coding strand.
+fn upstream<'a>(contig: &Contig<'a>, start: usize, forward: bool) -> Cow<'a, [u8]> {
+    if forward {
+        contig.slice((start - 1).saturating_sub(21), start - 1)
     } else {
-        Some(vec.iter().sum::<f64>() / vec.len() as f64)
+        Cow::Owned(seq::revcomp_seq(&contig.slice(start + 2, contig.seq.len().min(start + 23))))
     }
 }
 
-pub fn score_rbs(seq: &str) -> u32 {
-    let s: Vec<u8> = seq.as_bytes().iter().rev().copied().collect();
-
-    let scan = |patterns: &[&[u8]], ranges: &[(usize, usize)]| {
-        for &(start, end) in ranges {
-            if end > s.len() { continue; }
-            for pat in patterns {
-                if &s[start..end] == *pat {
-                    return true;
-                }
-            }
-        }
-        false
-    };
-
-    let p = |s: &str| s.to_ascii_uppercase().as_bytes().to_vec();
-
-    if scan(&[&p("GGAGGA")], &[(5,11), (6,12), (7,13), (8,14), (9,15), (10,16)]) { return 27; }
-    else if scan(&[&p("GGAGGA")], &[(3,9), (4,10)]) { return 26; }
-    else if scan(&[&p("GGAGGA")], &[(11,17), (12,18)]) { return 25; }
-    else if scan(&[&p("GGAGG")], &[(5,10), (6,11), (7,12), (8,13), (9,14), (10,15)]) { return 24; }
-    else if scan(&[&p("GGAGG")], &[(3,8), (4,9)]) { return 23; }
-    else if scan(&[&p("GAGGA")], &[(5,10), (6,11), (7,12), (8,13), (9,14), (10,15)]) { return 22; }
-    else if scan(&[&p("GAGGA")], &[(3,8), (4,9)]) { return 21; }
-    else if scan(&[&p("GAGGA"), &p("GGAGG")], &[(11,16), (12,17)]) { return 20; }
-    else if scan(&[&p("GGACGA"), &p("GGATGA"), &p("GGAAGA"), &p("GGCGGA"), &p("GGGGGA"), &p("GGTGGA")], &[(5,11), (6,12), (7,13), (8,14), (9,15), (10,16)]) { return 19; }
-    else if scan(&[&p("GGAAGA"), &p("GGATGA"), &p("GGACGA"), &p("GGTGGA"), &p("GGGGGA"), &p("GGCGGA")], &[(3,9), (4,10)]) { return 18; }
-    else if scan(&[&p("GGAAGA"), &p("GGATGA"), &p("GGACGA"), &p("GGTGGA"), &p("GGGGGA"), &p("GGCGGA")], &[(11,17), (12,18)]) { return 17; }
-    else if scan(&[&p("GGAG"), &p("GAGG")], &[(5,9), (6,10), (7,11), (8,12), (9,13), (10,14)]) { return 16; }
-    else if scan(&[&p("AGGA")], &[(5,9), (6,10), (7,11), (8,12), (9,13), (10,14)]) { return 15; }
-    else if scan(&[&p("GGTGG"), &p("GGGGG"), &p("GGCGG")], &[(5,10), (6,11), (7,12), (8,13), (9,14), (10,15)]) { return 14; }
-    else if scan(&[&p("AGG"), &p("GAG"), &p("GGA")], &[(5,8), (6,9), (7,10), (8,11), (9,12), (10,13)]) { return 13; }
-    else if scan(&[&p("AGGA"), &p("GAGG"), &p("GGAG")], &[(11,15), (12,16)]) { return 12; }
-    else if scan(&[&p("AGGA"), &p("GAGG"), &p("GGAG")], &[(3,7), (4,8)]) { return 11; }
-    else if scan(&[&p("GAGGA"), &p("GGAGG")], &[(13,18), (14,19), (15,20)]) || scan(&[&p("GGAGGA")], &[(13,19), (14,20), (15,21)]) { return 10; }
-    else if scan(&[&p("GAAGA"), &p("GATGA"), &p("GACGA")], &[(5,10), (6,11), (7,12), (8,13), (9,14), (10,15)]) { return 9; }
-    else if scan(&[&p("GGTGG"), &p("GGGGG"), &p("GGCGG")], &[(3,8), (4,9)]) { return 8; }
-    else if scan(&[&p("GGTGG"), &p("GGGGG"), &p("GGCGG")], &[(11,16), (12,17)]) { return 7; }
-    else if scan(&[&p("AGG"), &p("GAG"), &p("GGA")], &[(11,14), (12,15)]) { return 6; }
-    else if scan(&[&p("GAAGA"), &p("GATGA"), &p("GACGA")], &[(3,8), (4,9)]) { return 5; }
-    else if scan(&[&p("GAAGA"), &p("GATGA"), &p("GACGA")], &[(11,16), (12,17)]) { return 4; }
-    else if scan(&[&p("AGGA"), &p("GAGG"), &p("GGAG")], &[(13,17), (14,18), (15,19)]) { return 3; }
-    else if scan(&[&p("AGG"), &p("GAG"), &p("GGA")], &[(13,16), (14,17), (15,18)]) { return 2; }
-    else if scan(&[&p("GGAAGA"), &p("GGATGA"), &p("GGACGA")], &[(13,19), (14,20), (15,21)]) { return 2; }
-    else if scan(&[&p("GGTGG"), &p("GGGGG"), &p("GGCGG")], &[(13,18), (14,19), (15,20)]) { return 2; }
-    else if scan(&[&p("AGG"), &p("GAG"), &p("GGA")], &[(3,6), (4,7)]) { return 1; }
-
-    0
+/// The best RBS upstream of a start codon, as `(motif, score, spacer)`.
+fn find_rbs(rbs_model: &RbsModel, upstream: &[u8]) -> (String, f64, Option<usize>) {
+    match rbs_model.best_match(upstream) {
+        Some(found) => (found.motif, found.score as f64, Some(found.spacer)),
+        None => (String::new(), 0.0, None),
+    }
 }
 
-
+/// Enumerate every start-to-stop candidate in all six reading frames.
+///
+/// Coordinates follow PHANOTATE: positions are 1-based and always point at the
+/// leftmost base of a codon, so forward ORFs have `start < stop` and reverse
+/// ORFs have `stop < start`. The RBS of each ORF is the best class of
+/// `rbs_model` in the 21 bases upstream of its start codon, on the coding
+/// strand.
+///
+/// The contig is read from base `origin` (0-based) onwards and around its
+/// end, which cuts a circular genome there; coordinates count from that cut.
+///
+/// Codons are matched regardless of case, so soft-masked sequence is read like
+/// any other, and a codon with an ambiguous base is never a start or a stop.
+/// Runs of at least `seq::MIN_GAP_RUN` `N`s are assembly gaps of unknown
+/// length, which ORFs run into rather than end at: each frame closes on its
+/// last codon before a gap and opens again on its first codon past it,
+/// unless those are a real start or stop. Such ORFs fail `Orf::has_start` or
+/// `Orf::has_stop`. With `partial`, the ends of the contig are treated the
+/// same way.
+#[allow(clippy::too_many_arguments)]
 pub fn get_orfs<'a>(
     seq: &'a [u8],
-    start_codons: Option<&'a [&'a str]>,
-    stop_codons: Option<&'a [&'a str]>,
-) -> Orfs {
+    start_codons: Option<&[&str]>,
+    stop_codons: Option<&[&str]>,
+    min_orf_len: usize,
+    rbs_model: &RbsModel,
+    origin: usize,
+    partial: bool,
+) -> Orfs<'a> {
     let start = start_codons.unwrap_or(&["ATG", "GTG", "TTG"]);
     let stop = stop_codons.unwrap_or(&["TAG", "TGA", "TAA"]);
-    let mut open_reading_frames : Orfs;
-    open_reading_frames.seq = String::from_utf8_lossy(seq).to_string();
+    let mut open_reading_frames = Orfs::new(
+        min_orf_len,
+        start.iter().map(|c| c.to_uppercase()).collect(),
+        stop.iter().map(|c| c.to_uppercase()).collect(),
+    );
+    open_reading_frames.seq = seq;
+    open_reading_frames.origin = origin;
     open_reading_frames.contig_length = seq.len();
+    let contig = open_reading_frames.contig();
+    let gaps = seq::gap_mask(&contig.slice(0, seq.len()));
 
+    let is_in = |codons: &[&str], codon: &[u8]| codons.iter().any(|c| c.as_bytes().eq_ignore_ascii_case(codon));
+    let n = seq.len();
 
-    
-    // use `start` and `stop`...
-   open_reading_frames
-}
+    // the stretches of sequence between assembly gaps, 0-based and half-open
+    let mut stretches = Vec::new();
+    let mut from = 0;
+    for (i, _) in gaps.iter().chain([&true]).enumerate().filter(|(_, &gap)| gap) {
+        if i > from {
+            stretches.push((from, i));
+        }
+        from = i + 1;
+    }
 
-fn process_dna(dna: &str) -> (HashMap<char, u32>, Vec<f64>) {
-    let mut frequency: HashMap<char, u32> = HashMap::from([
-        ('A', 0), ('T', 0), ('C', 0), ('G', 0),
-    ]);
-    let mut background_rbs = vec![1.0; 28];
-    let mut training_rbs = vec![1.0; 28]; // unused in the Python version
-    let mut frame_plot = GCFrame::new(dna.len());
-
-    let dna_chars: Vec<char> = dna.chars().collect();
-
-    for (i, &base_raw) in dna_chars.iter().enumerate() {
-        let mut base = match base_raw.to_ascii_uppercase() {
-            'A' | 'T' | 'C' | 'G' => base_raw,
-            'S' | 'B' | 'V' => 'G',
-            _ => 'A',
-        };
-
-        // Nucleotide frequency
-        *frequency.entry(base).or_insert(0) += 1;
-        *frequency.entry(seq::revcomp_base(&base)).or_insert(0) += 1;
-
-        // RBS scoring with 21-mer
-        if i + 21 <= dna.len() {
-            let kmer: String = dna_chars[i..i+21].iter().collect();
-            let rev_kmer = seq::revcomp_kmer(&kmer);
-
-            let idx = score_rbs(&kmer);
-            if idx < background_rbs.len() {
-                background_rbs[idx] += 1.0;
+    for (from, to) in stretches {
+        // forward frames: starts seen since the last stop in each frame
+        // reverse frames: starts seen since the last stop, and that stop's position
+        let mut starts: [Vec<usize>; 3] = Default::default();
+        let mut rev_starts: [Vec<usize>; 3] = Default::default();
+        let mut rev_stops: [Option<usize>; 3] = [None; 3];
+        let (open_left, open_right) = (partial || from > 0, partial || to < n);
+
+        if open_left {
+            for first in (from + 1..from + 4).filter(|&first| first + 2 <= to) {
+                let codon = contig.slice(first - 1, first + 2);
+                let frame = (first - 1) % 3;
+                if !is_in(start, &codon) {
+                    starts[frame].push(first);
+                }
+                if !is_in(stop, &seq::revcomp_seq(&codon)) {
+                    rev_stops[frame] = Some(first);
+                }
             }
+        }
+
+        for i in from + 1..to.saturating_sub(1) {
+            let codon = &*contig.slice(i - 1, i + 2);
+            let mut rev_codon = [0; 3];
+            rev_codon.copy_from_slice(codon);
+            seq::revcomp_in_place(&mut rev_codon);
+            let frame = (i - 1) % 3;
 
-            let idx_rc = score_rbs(&rev_kmer);
-            if idx_rc < background_rbs.len() {
-                background_rbs[idx_rc] += 1.0;
+            if is_in(start, codon) {
+                starts[frame].push(i);
             }
+            if is_in(start, &rev_codon) {
+                rev_starts[frame].push(i);
+            }
+            if is_in(stop, codon) {
+                add_forward_orfs(&mut open_reading_frames, &contig, &starts[frame], i, frame, rbs_model);
+                starts[frame].clear();
+            }
+            if is_in(stop, &rev_codon) {
+                if let Some(orf_stop) = rev_stops[frame] {
+                    add_reverse_orfs(&mut open_reading_frames, &contig, &rev_starts[frame], orf_stop, frame, rbs_model);
+                }
+                rev_starts[frame].clear();
+                rev_stops[frame] = Some(i);
+            }
+        }
+
+        // reverse ORFs are only closed by the next stop, so flush the last ones
+        for frame in 0..3 {
+            if open_right && to >= frame + 3 {
+                let last = to - 2 - (to - 3 - frame) % 3;
+                if !starts[frame].is_empty() {
+                    add_forward_orfs(&mut open_reading_frames, &contig, &starts[frame], last, frame, rbs_model);
+                }
+                if rev_stops[frame].is_some_and(|stop| stop != last) && rev_starts[frame].last() != Some(&last) {
+                    rev_starts[frame].push(last);
+                }
+            }
+            if let Some(orf_stop) = rev_stops[frame] {
+                add_reverse_orfs(&mut open_reading_frames, &contig, &rev_starts[frame], orf_stop, frame, rbs_model);
+            }
+        }
+    }
+
+    open_reading_frames
+}
+
+/// Weight every ORF by how unlikely it is to be open by chance, by its GC
+/// frame plot, by its RBS and by its start codon weight. ORFs that run off
+/// the contig or into a gap before a start codon have neither an RBS nor a
+/// start codon to weigh, so both leave their weight alone. `pstop` is the
+/// chance of a codon being a stop, see `Orfs::pstop`.
+pub fn score_orfs(orfs: &mut Orfs, start_codon_weight: &HashMap<String, f64>, rbs_model: &RbsModel, pstop: f64) {
+    let (gc_pos_freq, background_rbs) = process_dna(&orfs.contig().slice(0, orfs.contig_length), rbs_model);
+    let rbs_log_odds = train_rbs(orfs, &background_rbs);
+
+    // the codon positions where GC peaks and dips across all ORFs, which the
+    // long, mostly real, genes dominate
+    let counts: Vec<([f64; 4], [f64; 4])> = orfs.iter_orfs().map(|orf| gc_frame_counts(orf, &gc_pos_freq)).collect();
+    let mut all_maxs = [0.0; 4];
+    let mut all_mins = [0.0; 4];
+    for (maxs, mins) in &counts {
+        for i in 1..4 {
+            all_maxs[i] += maxs[i];
+            all_mins[i] += mins[i];
+        }
+    }
+    let peak = (1..4).max_by(|&a, &b| all_maxs[a].total_cmp(&all_maxs[b])).unwrap();
+    let dip = (1..4).max_by(|&a, &b| all_mins[a].total_cmp(&all_mins[b])).unwrap();
+
+    for (orf, (maxs, mins)) in orfs.iter_orfs_mut().zip(counts) {
+        let codons = (orf.length / 3) as f64;
+        orf.hold = (1.0 - pstop).powf(codons).max(f64::MIN_POSITIVE);
+        let total = maxs[1] + maxs[2] + maxs[3];
+        orf.gcfp_maxs = maxs[peak] / total;
+        orf.gcfp_mins = mins[dip] / total;
+        orf.hold *= (1.0 / 3.0) / orf.gcfp_maxs * (1.0 / 3.0) / orf.gcfp_mins;
+        orf.weight_rbs = if orf.has_start() { rbs_log_odds[orf.rbs_score as usize].exp() } else { 1.0 };
+        orf.score(start_codon_weight);
+    }
+}
+
+/// The longest ORF of every stop that reaches `RBS_TRAINING_MIN_LEN`, as
+/// such long ORFs are almost always genes, leaving out those with no start
+/// codon and so no RBS.
+fn training_orfs<'o, 'a>(orfs: &'o Orfs<'a>) -> impl Iterator<Item = &'o Orf<'a>> {
+    orfs.data
+        .values()
+        .filter_map(|by_start| by_start.values().filter(|orf| orf.has_start()).max_by_key(|orf| orf.length))
+        .filter(|orf| orf.length >= RBS_TRAINING_MIN_LEN)
+}
+
+/// Per-class log-odds of the RBS classes upstream of real genes
+/// against the whole genome.
+///
+/// The foreground is taken from the `training_orfs`; the background counts
+/// every 21-mer on both strands. Both start from a pseudocount of one.
+pub fn train_rbs(orfs: &Orfs, background_rbs: &[f64]) -> Vec<f64> {
+    let mut training_rbs = vec![1.0; background_rbs.len()];
+    for orf in training_orfs(orfs) {
+        training_rbs[orf.rbs_score as usize] += 1.0;
+    }
+
+    let training_total: f64 = training_rbs.iter().sum();
+    let background_total: f64 = background_rbs.iter().sum();
+    training_rbs
+        .iter()
+        .zip(background_rbs)
+        .map(|(fg, bg)| ((fg / training_total) / (bg / background_total)).ln())
+        .collect()
+}
+
+/// Learn a k-mer RBS model from the regions upstream of the `training_orfs`
+/// against both strands of the whole contig, for hosts whose genes carry no
+/// Shine-Dalgarno motif, and rescore the RBS of every ORF with it.
+pub fn learn_rbs(orfs: &mut Orfs) -> RbsModel {
+    let contig = orfs.contig();
+    let seq = orfs.seq;
+    let revcomp = seq::revcomp_seq(seq);
+    let foreground: Vec<Cow<[u8]>> = training_orfs(orfs)
+        .map(|orf| upstream(&contig, orf.start, orf.frame > 0))
+        .collect();
+    let model = RbsModel::Kmers(KmerTable::learn(
+        RBS_DENOVO_K,
+        foreground.iter().map(|up| up.as_ref()),
+        [seq, revcomp.as_slice()],
+    )
+    .expect("RBS_DENOVO_K is a valid k-mer length"));
+
+    for orf in orfs.iter_orfs_mut() {
+        let (rbs, rbs_score, rbs_spacer) = find_rbs(&model, &upstream(&contig, orf.start, orf.frame > 0));
+        orf.rbs = rbs;
+        orf.rbs_score = rbs_score;
+        orf.rbs_spacer = rbs_spacer;
+    }
+    model
+}
+
+/// Count on which codon position (1-3) the GC frame plot peaks and dips
+/// along an ORF, with a pseudocount of one. Genes keep a steady difference in
+/// GC between codon positions, so in the true frame most codons agree with
+/// the genome-wide peak and dip, while shadow ORFs in other frames do not.
+fn gc_frame_counts(orf: &Orf, gc_pos_freq: &[[usize; 3]]) -> ([f64; 4], [f64; 4]) {
+    let mut maxs = [1.0; 4];
+    let mut mins = [1.0; 4];
+    let left = orf.start.min(orf.stop);
+    let right = orf.start.max(orf.stop);
+    // skip the stop codon, which lies at the left end of reverse ORFs
+    let codons = if orf.frame > 0 { (left..right).step_by(3) } else { (left + 3..right + 3).step_by(3) };
+    for base in codons {
+        let Some(&[a, b, c]) = gc_pos_freq.get(base + GC_WINDOW / 2) else { break };
+        // codon position 1 is the rightmost base of a reverse strand codon
+        let (first, second, third) = if orf.frame > 0 { (a, b, c) } else { (c, b, a) };
+        maxs[max_idx(first, second, third)] += 1.0;
+        mins[min_idx(first, second, third)] += 1.0;
+    }
+    (maxs, mins)
+}
+
+fn add_forward_orfs<'a>(orfs: &mut Orfs<'a>, contig: &Contig<'a>, starts: &[usize], orf_stop: usize, frame: usize, rbs_model: &RbsModel) {
+    for &orf_start in starts.iter().rev() {
+        let length = orf_stop + 3 - orf_start;
+        if length >= orfs.min_orf_len {
+            let (rbs, rbs_score, rbs_spacer) = find_rbs(rbs_model, &upstream(contig, orf_start, true));
+            let orf_seq = contig.slice(orf_start - 1, orf_stop + 2);
+            orfs.add_orf(orf_start, orf_stop, length, (frame + 1) as isize, orf_seq, rbs, rbs_score)
+                .rbs_spacer = rbs_spacer;
         }
+    }
+}
 
-        // GC frame plot
-        frame_plot.add_base(base);
+fn add_reverse_orfs(orfs: &mut Orfs, contig: &Contig, starts: &[usize], orf_stop: usize, frame: usize, rbs_model: &RbsModel) {
+    for &orf_start in starts {
+        let length = orf_start + 3 - orf_stop;
+        if length >= orfs.min_orf_len {
+            let (rbs, rbs_score, rbs_spacer) = find_rbs(rbs_model, &upstream(contig, orf_start, false));
+            let orf_seq = Cow::Owned(seq::revcomp_seq(&contig.slice(orf_stop - 1, orf_start + 2)));
+            orfs.add_orf(orf_start, orf_stop, length, -((frame + 1) as isize), orf_seq, rbs, rbs_score)
+                .rbs_spacer = rbs_spacer;
+        }
     }
+}
 
-    let gc_pos_freq = frame_plot.get();
+/// The GC frame plot of a contig and the RBS classes of every 21-mer on
+/// both of its strands, counted from a pseudocount of one.
+fn process_dna(dna: &[u8], rbs_model: &RbsModel) -> (Vec<[usize; 3]>, Vec<f64>) {
+    let mut background_rbs = vec![1.0; rbs_model.max_score() as usize + 1];
+    let frame_plot = GCFrame::new(GC_WINDOW);
 
-    (frequency, gc_pos_freq)
-}
\ No newline at end of file
+    // ambiguous bases are left as they are: they match no RBS motif, and
+    // only S, certain to be G or C, counts towards the GC frame plot
+    let bases: Vec<u8> = dna
+        .iter()
+        .map(|base_raw| match base_raw.to_ascii_uppercase() {
+            b'S' => b'G',
+            base => base,
+        })
+        .collect();
+
+    let mut rev_kmer = [0; 21];
+    for kmer in bases.windows(21) {
+        background_rbs[rbs_model.score(kmer) as usize] += 1.0;
+        rev_kmer.copy_from_slice(kmer);
+        seq::revcomp_in_place(&mut rev_kmer);
+        background_rbs[rbs_model.score(&rev_kmer) as usize] += 1.0;
+    }
+
+    let gc_pos_freq = frame_plot.get(&bases);
+
+    (gc_pos_freq, background_rbs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orfs(dna: &[u8], min_orf_len: usize) -> Orfs<'_> {
+        get_orfs(dna, None, None, min_orf_len, RbsModel::default_model(), 0, false)
+    }
+
+    /// A 96 bp gene: ATG, ten GCT codons, a GTG start, 19 more GCT codons and
+    /// TAA.
+    fn gene() -> Vec<u8> {
+        [b"ATG".as_slice(), &b"GCT".repeat(10), b"GTG", &b"GCT".repeat(19), b"TAA"].concat()
+    }
+
+    #[test]
+    fn finds_orfs_on_both_strands() {
+        let dna = [gene(), b"TTAG".to_vec(), seq::revcomp_seq(&gene())].concat();
+        let orfs = orfs(&dna, 90);
+
+        let forward = orfs.get_orf(1, 94).unwrap();
+        assert_eq!((forward.length, forward.frame), (96, 1));
+        assert_eq!(forward.seq.as_ref(), gene().as_slice());
+        assert!(orfs.get_orf(34, 94).is_none());
+
+        // the reverse gene takes up 101..196, its stop codon on the left
+        let reverse = orfs.get_orf(194, 101).unwrap();
+        assert_eq!((reverse.length, reverse.frame), (96, -2));
+        assert_eq!(reverse.seq.as_ref(), gene().as_slice());
+    }
+
+    #[test]
+    fn drops_orfs_shorter_than_the_minimum() {
+        let dna = gene();
+        assert!(orfs(&dna, 96).get_orf(1, 94).is_some());
+        assert!(orfs(&dna, 99).get_orf(1, 94).is_none());
+        assert_eq!(orfs(&dna, 63).get_orf(34, 94).map(|orf| orf.length), Some(63));
+    }
+
+    #[test]
+    fn other_end_maps_stops_to_their_longest_orf() {
+        let dna = [gene(), b"TTAG".to_vec(), seq::revcomp_seq(&gene())].concat();
+        let orfs = orfs(&dna, 60);
+        assert_eq!(orfs.other_end.get(&94), Some(&1));
+        assert_eq!(orfs.other_end.get(&1), Some(&94));
+        assert_eq!(orfs.other_end.get(&34), Some(&94));
+        assert_eq!(orfs.other_end.get(&101), Some(&194));
+        assert_eq!(orfs.other_end.get(&194), Some(&101));
+        assert_eq!(orfs.other_end.get(&161), Some(&101));
+    }
+
+    #[test]
+    fn splits_orfs_at_assembly_gaps() {
+        // ATG and 40 GCT codons, ten Ns, 40 GCT codons and TAA
+        let dna = [b"ATG".as_slice(), &b"GCT".repeat(40), &[b'N'; 10], &b"GCT".repeat(40), b"TAA"].concat();
+        let forward = orfs(&dna, 90);
+        let left = forward.get_orf(1, 121).unwrap();
+        assert!(left.has_start() && !left.has_stop());
+        let right = forward.get_orf(134, 254).unwrap();
+        assert!(!right.has_start() && right.has_stop());
+
+        let rev = seq::revcomp_seq(&dna);
+        let reverse = orfs(&rev, 90);
+        let left = reverse.get_orf(121, 1).unwrap();
+        assert!(!left.has_start() && left.has_stop());
+        let right = reverse.get_orf(254, 134).unwrap();
+        assert!(right.has_start() && !right.has_stop());
+    }
+}
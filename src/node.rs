@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
@@ -48,3 +49,19 @@ impl Node {
         format!("{:?},{:?},{:?},{:?}", self.gene, self.node_type, self.frame, self.position)
     }
 }
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.position
+            .cmp(&other.position)
+            .then_with(|| self.node_type.cmp(&other.node_type))
+            .then_with(|| self.frame.cmp(&other.frame))
+            .then_with(|| self.gene.cmp(&other.gene))
+    }
+}
@@ -1,3 +1,5 @@
+use crate::seq;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
 
@@ -7,9 +9,10 @@ pub struct Orf<'a> {
     pub stop: usize,
     pub length: usize,
     pub frame: isize,
-    pub seq: &'a [u8],
+    pub seq: Cow<'a, [u8]>,
     pub rbs: String,
     pub rbs_score: f64,
+    pub rbs_spacer: Option<usize>,
     pub weight: f64,
     pub weight_start: f64,
     pub weight_rbs: f64,
@@ -21,7 +24,8 @@ pub struct Orf<'a> {
 }
 
 impl<'a> Orf<'a> {
-    pub fn new(start: usize, stop: usize, length: usize, frame: isize, seq: &'a [u8], rbs: String, rbs_score: f64, start_codons: Vec<String>, stop_codons: Vec<String>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(start: usize, stop: usize, length: usize, frame: isize, seq: Cow<'a, [u8]>, rbs: String, rbs_score: f64, start_codons: Vec<String>, stop_codons: Vec<String>) -> Self {
         Self {
             start,
             stop,
@@ -30,6 +34,7 @@ impl<'a> Orf<'a> {
             seq,
             rbs,
             rbs_score,
+            rbs_spacer: None,
             weight: 1.0,
             weight_start: 1.0,
             weight_rbs: 1.0,
@@ -49,17 +54,21 @@ impl<'a> Orf<'a> {
         std::str::from_utf8(&self.seq[self.seq.len()-3..]).unwrap()
     }
 
+    /// Whether the ORF opens on a start codon, which it lacks when it runs
+    /// off the contig or into an assembly gap before one, see `get_orfs`.
     pub fn has_start(&self) -> bool {
-        self.start_codons.contains(&self.start_codon().to_string())
+        self.start_codons.iter().any(|c| c.eq_ignore_ascii_case(self.start_codon()))
     }
 
+    /// Whether the ORF closes on a stop codon, which it lacks when it runs
+    /// off the contig or into an assembly gap before one.
     pub fn has_stop(&self) -> bool {
-        self.stop_codons.contains(&self.stop_codon().to_string())
+        self.stop_codons.iter().any(|c| c.eq_ignore_ascii_case(self.stop_codon()))
     }
 
     pub fn score(&mut self, start_codon_weight: &HashMap<String, f64>) {
         let mut s = 1.0 / self.hold;
-        if let Some(w) = start_codon_weight.get(self.start_codon()) {
+        if let Some(w) = start_codon_weight.get(&self.start_codon().to_ascii_uppercase()) {
             s *= w;
         }
         s *= self.weight_rbs;
@@ -74,6 +83,36 @@ impl<'a> fmt::Display for Orf<'a> {
     }
 }
 
+/// A contig read from base `origin` onwards, wrapping around its end, so
+/// circular genomes can be cut anywhere. Positions on it are 1-based like ORF
+/// coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct Contig<'a> {
+    pub seq: &'a [u8],
+    pub origin: usize,
+}
+
+impl<'a> Contig<'a> {
+    /// Bases `from..to`, 0-based, borrowed unless they cross the end of the
+    /// underlying sequence.
+    pub fn slice(&self, from: usize, to: usize) -> Cow<'a, [u8]> {
+        let n = self.seq.len();
+        let (from, to) = (from + self.origin, to + self.origin);
+        if to <= n {
+            Cow::Borrowed(&self.seq[from..to])
+        } else if from >= n {
+            Cow::Borrowed(&self.seq[from - n..to - n])
+        } else {
+            Cow::Owned([&self.seq[from..], &self.seq[..to - n]].concat())
+        }
+    }
+
+    /// Position on the underlying sequence of `position` on this one.
+    pub fn original_position(&self, position: usize) -> usize {
+        (position - 1 + self.origin) % self.seq.len() + 1
+    }
+}
+
 pub struct Orfs<'a> {
     pub data: HashMap<usize, HashMap<usize, Orf<'a>>>,
     pub other_end: HashMap<usize, usize>,
@@ -81,7 +120,10 @@ pub struct Orfs<'a> {
     pub stop_codons: Vec<String>,
     pub min_orf_len: usize,
     pub contig_length: usize,
-    pub seq: String,
+    /// the contig, borrowed from the caller
+    pub seq: &'a [u8],
+    /// base of `seq` that ORF coordinates count from, see `Contig`
+    pub origin: usize,
 }
 
 impl<'a> Orfs<'a> {
@@ -93,22 +135,57 @@ impl<'a> Orfs<'a> {
             stop_codons,
             min_orf_len,
             contig_length: 0,
-            seq: String::new(),
+            seq: &[],
+            origin: 0,
         }
     }
 
-    pub fn add_orf(&mut self, start: usize, stop: usize, length: usize, frame: isize, seq: &'a [u8], rbs: String, rbs_score: f64) {
+    pub fn contig(&self) -> Contig<'a> {
+        Contig { seq: self.seq, origin: self.origin }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_orf(&mut self, start: usize, stop: usize, length: usize, frame: isize, seq: Cow<'a, [u8]>, rbs: String, rbs_score: f64) -> &mut Orf<'a> {
         let orf = Orf::new(start, stop, length, frame, seq, rbs, rbs_score, self.start_codons.clone(), self.stop_codons.clone());
-        self.data.entry(stop).or_insert_with(HashMap::new).insert(start, orf);
         self.other_end.insert(start, stop);
         self.other_end.insert(stop, start);
+        let by_start = self.data.entry(stop).or_default();
+        by_start.insert(start, orf);
+        by_start.get_mut(&start).unwrap()
     }
 
     pub fn iter_orfs(&self) -> impl Iterator<Item = &Orf<'a>> {
         self.data.values().flat_map(|map| map.values())
     }
 
+    pub fn iter_orfs_mut(&mut self) -> impl Iterator<Item = &mut Orf<'a>> {
+        self.data.values_mut().flat_map(|map| map.values_mut())
+    }
+
     pub fn get_orf(&self, start: usize, stop: usize) -> Option<&Orf<'a>> {
         self.data.get(&stop)?.get(&start)
     }
+
+    /// Probability of drawing a stop codon at random given the base
+    /// composition of both strands of the contig.
+    pub fn pstop(&self) -> f64 {
+        let mut counts: HashMap<u8, f64> = HashMap::new();
+        for base in self.seq.iter().map(u8::to_ascii_uppercase).filter(|&base| seq::is_unambiguous(base)) {
+            *counts.entry(base).or_insert(0.0) += 1.0;
+            *counts.entry(seq::complement(base)).or_insert(0.0) += 1.0;
+        }
+        let total: f64 = counts.values().sum();
+        if total == 0.0 {
+            return 0.0;
+        }
+        self.stop_codons
+            .iter()
+            .map(|codon| {
+                codon
+                    .bytes()
+                    .map(|b| counts.get(&b).copied().unwrap_or(0.0) / total)
+                    .product::<f64>()
+            })
+            .sum()
+    }
 }
\ No newline at end of file
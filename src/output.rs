@@ -0,0 +1,320 @@
+use crate::annotator::Gene;
+use crate::repeats::TerminalRepeat;
+use crate::translate::GeneticCode;
+use std::io::{self, Write};
+
+/// Format a float the way Python's `str()` does, so scores diff cleanly
+/// against the original PHANOTATE output.
+pub fn format_score(score: f64) -> String {
+    let magnitude = score.abs();
+    if score.is_finite() && magnitude != 0.0 && !(1e-4..1e16).contains(&magnitude) {
+        let s = format!("{:e}", score);
+        let (mantissa, exponent) = s.split_once('e').unwrap();
+        match exponent.strip_prefix('-') {
+            Some(exp) => format!("{}e-{:0>2}", mantissa, exp),
+            None => format!("{}e+{:0>2}", mantissa, exponent),
+        }
+    } else if score.is_finite() && score.fract() == 0.0 {
+        format!("{:.1}", score)
+    } else {
+        format!("{}", score)
+    }
+}
+
+pub fn write_tabular<W: Write + ?Sized>(writer: &mut W, contig: &str, genes: &[Gene]) -> io::Result<()> {
+    writeln!(writer, "#id:\t{}", contig)?;
+    writeln!(writer, "#START\tSTOP\tFRAME\tCONTIG\tSCORE")?;
+    for gene in genes {
+        let strand = if gene.is_forward() { '+' } else { '-' };
+        writeln!(writer, "{}\t{}\t{}\t{}\t{}", gene.start, gene.stop, strand, contig, format_score(gene.score))?;
+    }
+    Ok(())
+}
+
+/// Wrap a qualifier so every line fits the 79 column GenBank layout.
+fn write_qualifier<W: Write + ?Sized>(writer: &mut W, qualifier: &str) -> io::Result<()> {
+    let indent = " ".repeat(21);
+    let bytes = qualifier.as_bytes();
+    for chunk in bytes.chunks(58) {
+        writeln!(writer, "{}{}", indent, String::from_utf8_lossy(chunk))?;
+    }
+    Ok(())
+}
+
+/// Prodigal's flags for a gene running off the contig or into a gap on its
+/// left and right, e.g. `10` for one cut short on the left; `None` for whole
+/// genes.
+fn partial_flags(gene: &Gene) -> Option<String> {
+    let (left, right) = (gene.partial_left(), gene.partial_right());
+    (left || right).then(|| format!("{}{}", left as u8, right as u8))
+}
+
+/// GenBank location of a gene, joined across the origin when it wraps
+/// around a circular genome of `circular_length` bases, and marked `<` or
+/// `>` where it runs off the end of the contig or into a gap.
+fn genbank_location(gene: &Gene, circular_length: usize) -> String {
+    let span = if gene.wraps_origin() {
+        format!("join({}..{},1..{})", gene.left(), circular_length, gene.right())
+    } else {
+        let open_left = if gene.partial_left() { "<" } else { "" };
+        let open_right = if gene.partial_right() { ">" } else { "" };
+        format!("{}{}..{}{}", open_left, gene.left(), open_right, gene.right())
+    };
+    if gene.is_forward() {
+        span
+    } else {
+        format!("complement({})", span)
+    }
+}
+
+/// Write a contig as a GenBank record. `circular` is the length of the
+/// circular genome when the contig is one, which a trimmed terminal repeat
+/// makes shorter than the contig: the genome holds the repeat once, so it is
+/// written without the right copy.
+pub fn write_genbank<W: Write + ?Sized>(
+    writer: &mut W,
+    contig: &str,
+    dna: &[u8],
+    circular: Option<usize>,
+    repeat: Option<&TerminalRepeat>,
+    genes: &[Gene],
+    code: &GeneticCode,
+) -> io::Result<()> {
+    let contig_length = dna.len();
+    let dna = &dna[..circular.unwrap_or(contig_length)];
+    let topology = if circular.is_some() { "circular" } else { "linear" };
+    writeln!(writer, "LOCUS       {:<16} {:>11} bp    DNA     {:<8} PHG", contig, dna.len(), topology)?;
+    writeln!(writer, "DEFINITION  {}", contig)?;
+    writeln!(writer, "FEATURES             Location/Qualifiers")?;
+    writeln!(writer, "     source          1..{}", dna.len())?;
+    if let Some(repeat) = repeat {
+        for (left, right) in repeat.spans(contig_length).into_iter().filter(|&(_, right)| right <= dna.len()) {
+            writeln!(writer, "     repeat_region   {}..{}", left, right)?;
+            write_qualifier(writer, "/rpt_type=terminal")?;
+            write_qualifier(writer, &format!("/note=\"direct terminal repeat, {} mismatches\"", repeat.mismatches))?;
+        }
+    }
+    for gene in genes {
+        writeln!(writer, "     CDS             {}", genbank_location(gene, dna.len()))?;
+        write_qualifier(writer, &format!("/note=\"score={}\"", format_score(gene.score)))?;
+        write_qualifier(writer, "/codon_start=1")?;
+        write_qualifier(writer, &format!("/transl_table={}", code.id))?;
+        write_qualifier(writer, &format!("/translation=\"{}\"", gene.translation))?;
+    }
+    writeln!(writer, "ORIGIN")?;
+    for (i, line) in dna.chunks(60).enumerate() {
+        write!(writer, "{:>9}", i * 60 + 1)?;
+        for block in line.chunks(10) {
+            write!(writer, " {}", String::from_utf8_lossy(block).to_ascii_lowercase())?;
+        }
+        writeln!(writer)?;
+    }
+    writeln!(writer, "//")
+}
+
+fn write_fasta_header<W: Write + ?Sized>(writer: &mut W, contig: &str, gene: &Gene) -> io::Result<()> {
+    write!(
+        writer,
+        ">{}_CDS_[{}..{}] [frame={:+}] [score={}]",
+        contig, gene.start, gene.stop, gene.frame, format_score(gene.score)
+    )?;
+    match partial_flags(gene) {
+        Some(flags) => writeln!(writer, " [partial={}]", flags),
+        None => writeln!(writer),
+    }
+}
+
+pub fn write_fasta<W: Write + ?Sized>(writer: &mut W, contig: &str, genes: &[Gene]) -> io::Result<()> {
+    for gene in genes {
+        write_fasta_header(writer, contig, gene)?;
+        for line in gene.seq.chunks(60) {
+            writeln!(writer, "{}", String::from_utf8_lossy(line))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_faa<W: Write + ?Sized>(writer: &mut W, contig: &str, genes: &[Gene]) -> io::Result<()> {
+    for gene in genes {
+        write_fasta_header(writer, contig, gene)?;
+        for line in gene.translation.as_bytes().chunks(60) {
+            writeln!(writer, "{}", String::from_utf8_lossy(line))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_gff3_header<W: Write + ?Sized>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "##gff-version 3")
+}
+
+/// Write the features of a contig. Genes that wrap around the origin of a
+/// circular genome of `circular` bases are split into two lines sharing one
+/// ID, and genes running off the contig or into a gap carry a `partial` flag.
+/// As in `write_genbank`, a circular genome is described without the trimmed
+/// copy of its terminal repeat.
+pub fn write_gff3<W: Write + ?Sized>(
+    writer: &mut W,
+    contig: &str,
+    contig_length: usize,
+    circular: Option<usize>,
+    repeat: Option<&TerminalRepeat>,
+    genes: &[Gene],
+) -> io::Result<()> {
+    let circular_length = circular.unwrap_or(contig_length);
+    writeln!(writer, "##sequence-region {} 1 {}", contig, circular_length)?;
+    if circular.is_some() {
+        writeln!(writer, "{}\tPHANOTATE\tregion\t1\t{}\t.\t+\t.\tID={};Is_circular=true", contig, circular_length, contig)?;
+    }
+    if let Some(repeat) = repeat {
+        let spans = repeat.spans(contig_length).into_iter().filter(|&(_, right)| right <= circular_length);
+        for (i, (left, right)) in spans.enumerate() {
+            writeln!(
+                writer,
+                "{}\tPHANOTATE\trepeat_region\t{}\t{}\t.\t+\t.\tID={}_DTR_{};rpt_type=terminal;mismatches={}",
+                contig,
+                left,
+                right,
+                contig,
+                i + 1,
+                repeat.mismatches,
+            )?;
+        }
+    }
+    for (i, gene) in genes.iter().enumerate() {
+        let strand = if gene.is_forward() { '+' } else { '-' };
+        let partial = partial_flags(gene).map_or(String::new(), |flags| format!(";partial={}", flags));
+        // genes with no RBS found carry only its score
+        let motif = gene.rbs_motif.as_ref().map_or(String::new(), |motif| format!(";rbs_motif={}", motif));
+        let spacer = gene.rbs_spacer.map_or(String::new(), |spacer| format!(";rbs_spacer={}bp", spacer));
+        // the phase of a part is the bases of a split codon it starts with,
+        // counted from the 5' end of the gene
+        let spans = if !gene.wraps_origin() {
+            vec![(gene.left(), gene.right(), 0)]
+        } else if gene.is_forward() {
+            let before = circular_length - gene.left() + 1;
+            vec![(gene.left(), circular_length, 0), (1, gene.right(), (3 - before % 3) % 3)]
+        } else {
+            let after = gene.right();
+            vec![(gene.left(), circular_length, (3 - after % 3) % 3), (1, gene.right(), 0)]
+        };
+        for (left, right, phase) in spans {
+            writeln!(
+                writer,
+                "{}\tPHANOTATE\tCDS\t{}\t{}\t{}\t{}\t{}\tID={}_CDS_{}{}{}{};rbs_score={}",
+                contig,
+                left,
+                right,
+                format_score(gene.score),
+                strand,
+                phase,
+                contig,
+                i + 1,
+                partial,
+                motif,
+                spacer,
+                gene.rbs_score,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gene(start: usize, stop: usize, frame: isize) -> Gene {
+        Gene {
+            start,
+            stop,
+            frame,
+            score: -1.5,
+            rbs_motif: None,
+            rbs_spacer: None,
+            rbs_score: 0.0,
+            seq: b"ATGGCTTAA".to_vec(),
+            translation: "MA".to_string(),
+            partial_start: false,
+            partial_stop: false,
+        }
+    }
+
+    fn written(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn formats_scores_like_python() {
+        let cases = [
+            (0.0, "0.0"),
+            (-3.0, "-3.0"),
+            (-0.5, "-0.5"),
+            (0.0001, "0.0001"),
+            (0.00001, "1e-05"),
+            (-1.5e-7, "-1.5e-07"),
+            (123456.789, "123456.789"),
+            (1e15, "1000000000000000.0"),
+            (1e16, "1e+16"),
+            (-2.5e20, "-2.5e+20"),
+            (1.2345678901234568e16, "1.2345678901234568e+16"),
+            (1e-300, "1e-300"),
+        ];
+        for (score, python) in cases {
+            assert_eq!(format_score(score), python);
+        }
+    }
+
+    #[test]
+    fn writes_genbank_records_joined_across_the_origin() {
+        let genes = [gene(901, 99, 1), gene(50, 950, -2), gene(201, 500, 3)];
+        let code = GeneticCode::new(11).unwrap();
+        let dna = b"ACGT".repeat(250);
+        let out = written(|w| write_genbank(w, "phage", &dna, Some(1000), None, &genes, &code));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "LOCUS       phage                   1000 bp    DNA     circular PHG");
+        let locations: Vec<&str> = lines.iter().filter_map(|line| line.strip_prefix("     CDS             ")).collect();
+        assert_eq!(locations, ["join(901..1000,1..99)", "complement(join(950..1000,1..50))", "201..500"]);
+        assert_eq!(lines[lines.len() - 2], "      961 acgtacgtac gtacgtacgt acgtacgtac gtacgtacgt");
+        assert_eq!(lines[lines.len() - 1], "//");
+
+        let out = written(|w| write_genbank(w, "phage", &dna[..900], None, None, &genes[2..], &code));
+        assert!(out.starts_with("LOCUS       phage                    900 bp    DNA     linear   PHG\n"));
+    }
+
+    #[test]
+    fn splits_gff3_features_across_the_origin() {
+        let mut genes = [gene(901, 99, 1), gene(50, 950, -2), gene(201, 500, 3)];
+        genes[2].rbs_motif = Some("GGAGG".to_string());
+        genes[2].rbs_spacer = Some(7);
+        genes[2].rbs_score = 24.0;
+        let out = written(|w| write_gff3(w, "phage", 1000, Some(1000), None, &genes));
+        let expected = [
+            "##sequence-region phage 1 1000",
+            "phage\tPHANOTATE\tregion\t1\t1000\t.\t+\t.\tID=phage;Is_circular=true",
+            "phage\tPHANOTATE\tCDS\t901\t1000\t-1.5\t+\t0\tID=phage_CDS_1;rbs_score=0",
+            "phage\tPHANOTATE\tCDS\t1\t99\t-1.5\t+\t2\tID=phage_CDS_1;rbs_score=0",
+            "phage\tPHANOTATE\tCDS\t950\t1000\t-1.5\t-\t1\tID=phage_CDS_2;rbs_score=0",
+            "phage\tPHANOTATE\tCDS\t1\t50\t-1.5\t-\t0\tID=phage_CDS_2;rbs_score=0",
+            "phage\tPHANOTATE\tCDS\t201\t500\t-1.5\t+\t0\tID=phage_CDS_3;rbs_motif=GGAGG;rbs_spacer=7bp;rbs_score=24",
+        ];
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn writes_fasta_headers() {
+        let mut genes = [gene(201, 500, 3), gene(99, 1, -1)];
+        genes[1].partial_start = true;
+        let expected = [
+            ">phage_CDS_[201..500] [frame=+3] [score=-1.5]",
+            "MA",
+            ">phage_CDS_[99..1] [frame=-1] [score=-1.5] [partial=01]",
+            "MA",
+        ];
+        assert_eq!(written(|w| write_faa(w, "phage", &genes)).lines().collect::<Vec<_>>(), expected);
+        let out = written(|w| write_fasta(w, "phage", &genes));
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected.map(|line| line.replace("MA", "ATGGCTTAA")));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::edges::Edge;
+use crate::node::Node;
+use std::collections::{HashMap, VecDeque};
+
+/// Find the minimum-weight path from `source` to `target`.
+///
+/// The gene graph is acyclic, so the nodes are put in topological order and
+/// every edge is relaxed exactly once, which is linear in the size of the
+/// graph and copes with the negative gene edges. Should a cycle turn up the
+/// solver falls back to Bellman-Ford. Returns `None` when the target cannot be
+/// reached or a negative cycle makes the shortest path undefined.
+///
+/// Gene weights span dozens of orders of magnitude, so path lengths are summed
+/// exactly: in plain `f64` every short gene after a long one rounds away and
+/// the path is free to drop them.
+pub fn get_path(edges: &[Edge<Node>], source: &Node, target: &Node) -> Option<Vec<Node>> {
+    let mut index: HashMap<&Node, usize> = HashMap::new();
+    let mut nodes: Vec<&Node> = Vec::new();
+    for node in [source, target]
+        .into_iter()
+        .chain(edges.iter().flat_map(|e| [&e.source, &e.target]))
+    {
+        index.entry(node).or_insert_with(|| {
+            nodes.push(node);
+            nodes.len() - 1
+        });
+    }
+
+    let mut adjacency: Vec<Vec<(usize, f64)>> = vec![Vec::new(); nodes.len()];
+    for edge in edges {
+        adjacency[index[&edge.source]].push((index[&edge.target], edge.weight.0));
+    }
+
+    let (s, t) = (index[source], index[target]);
+    let predecessor = match topological_order(&adjacency) {
+        Some(order) => dag_relax(&adjacency, &order, s),
+        None => bellman_ford(&adjacency, s)?,
+    };
+
+    let mut path = vec![t];
+    while let Some(&last) = path.last() {
+        if last == s {
+            break;
+        }
+        path.push(predecessor[last]?);
+    }
+    Some(path.into_iter().rev().map(|i| nodes[i].clone()).collect())
+}
+
+/// Kahn's algorithm; `None` if the graph has a cycle.
+fn topological_order(adjacency: &[Vec<(usize, f64)>]) -> Option<Vec<usize>> {
+    let mut indegree = vec![0usize; adjacency.len()];
+    for &(v, _) in adjacency.iter().flatten() {
+        indegree[v] += 1;
+    }
+
+    let mut queue: VecDeque<usize> = (0..adjacency.len()).filter(|&u| indegree[u] == 0).collect();
+    let mut order = Vec::with_capacity(adjacency.len());
+    while let Some(u) = queue.pop_front() {
+        order.push(u);
+        for &(v, _) in &adjacency[u] {
+            indegree[v] -= 1;
+            if indegree[v] == 0 {
+                queue.push_back(v);
+            }
+        }
+    }
+
+    (order.len() == adjacency.len()).then_some(order)
+}
+
+/// Relax the edge `u -> v`, returning whether it shortened the path to `v`.
+fn relax(distance: &mut [Option<Sum>], u: usize, v: usize, w: f64) -> bool {
+    let Some(through_u) = distance[u].as_ref().map(|d| d.add(w)) else {
+        return false;
+    };
+    if distance[v].as_ref().is_none_or(|d| through_u.less_than(d)) {
+        distance[v] = Some(through_u);
+        return true;
+    }
+    false
+}
+
+fn dag_relax(adjacency: &[Vec<(usize, f64)>], order: &[usize], s: usize) -> Vec<Option<usize>> {
+    let mut distance: Vec<Option<Sum>> = vec![None; adjacency.len()];
+    let mut predecessor = vec![None; adjacency.len()];
+    distance[s] = Some(Sum::default());
+
+    for &u in order {
+        for &(v, w) in &adjacency[u] {
+            if relax(&mut distance, u, v, w) {
+                predecessor[v] = Some(u);
+            }
+        }
+    }
+
+    predecessor
+}
+
+fn bellman_ford(adjacency: &[Vec<(usize, f64)>], s: usize) -> Option<Vec<Option<usize>>> {
+    let mut distance: Vec<Option<Sum>> = vec![None; adjacency.len()];
+    let mut predecessor = vec![None; adjacency.len()];
+    distance[s] = Some(Sum::default());
+
+    for _ in 1..adjacency.len() {
+        let mut changed = false;
+        for (u, targets) in adjacency.iter().enumerate() {
+            for &(v, w) in targets {
+                if relax(&mut distance, u, v, w) {
+                    predecessor[v] = Some(u);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return Some(predecessor);
+        }
+    }
+
+    // one more improving pass means a negative cycle is reachable
+    for (u, targets) in adjacency.iter().enumerate() {
+        for &(v, w) in targets {
+            if relax(&mut distance, u, v, w) {
+                return None;
+            }
+        }
+    }
+    Some(predecessor)
+}
+
+/// An exact sum of floats, kept as a Shewchuk expansion: non-overlapping
+/// components in increasing order of magnitude, the same idea as Python's
+/// `math.fsum`.
+#[derive(Clone, Debug, Default)]
+struct Sum(Vec<f64>);
+
+impl Sum {
+    fn add(&self, x: f64) -> Sum {
+        let mut components = Vec::with_capacity(self.0.len() + 1);
+        let mut q = x;
+        for &c in &self.0 {
+            let sum = q + c;
+            let b = sum - q;
+            let error = (q - (sum - b)) + (c - b);
+            if error != 0.0 {
+                components.push(error);
+            }
+            q = sum;
+        }
+        if q != 0.0 || components.is_empty() {
+            components.push(q);
+        }
+        Sum(components)
+    }
+
+    fn less_than(&self, other: &Sum) -> bool {
+        let difference = other.0.iter().fold(self.clone(), |d, &c| d.add(-c));
+        difference.0.last().is_some_and(|&x| x < 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(position: i32) -> Node {
+        Node::new("CDS", "start", 1, position)
+    }
+
+    fn graph(edges: &[(i32, i32, f64)]) -> Vec<Edge<Node>> {
+        edges.iter().map(|&(u, v, w)| Edge::new(node(u), node(v), w)).collect()
+    }
+
+    fn positions(path: Option<Vec<Node>>) -> Option<Vec<i32>> {
+        path.map(|nodes| nodes.iter().map(|n| n.position).collect())
+    }
+
+    #[test]
+    fn follows_negative_edges() {
+        // a greedy pick of the cheapest first edge would take 0 -> 2
+        let edges = graph(&[(0, 1, 2.0), (0, 2, 1.0), (1, 2, -3.0), (2, 3, 1.0)]);
+        assert_eq!(positions(get_path(&edges, &node(0), &node(3))), Some(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn unreachable_target_has_no_path() {
+        let edges = graph(&[(0, 1, 1.0), (2, 3, 1.0)]);
+        assert_eq!(positions(get_path(&edges, &node(0), &node(3))), None);
+        assert_eq!(positions(get_path(&edges, &node(0), &node(4))), None);
+    }
+
+    #[test]
+    fn falls_back_to_bellman_ford_on_cycles() {
+        let edges = graph(&[(0, 1, 1.0), (1, 2, 1.0), (2, 1, 1.0), (2, 3, 1.0), (0, 3, 5.0)]);
+        assert!(topological_order(&[vec![(1, 1.0)], vec![(0, 1.0)]]).is_none());
+        assert_eq!(positions(get_path(&edges, &node(0), &node(3))), Some(vec![0, 1, 2, 3]));
+
+        let negative_cycle = graph(&[(0, 1, 1.0), (1, 2, -1.0), (2, 1, -1.0), (2, 3, 1.0)]);
+        assert_eq!(positions(get_path(&negative_cycle, &node(0), &node(3))), None);
+    }
+
+    #[test]
+    fn sums_exactly() {
+        let sum = Sum::default().add(1e30).add(1.0).add(-1e30);
+        assert_eq!(sum.0, vec![1.0]);
+        assert!(Sum::default().add(0.5).less_than(&sum));
+        assert!(!sum.less_than(&Sum::default().add(1.0)));
+        assert!(!Sum::default().add(1.0).less_than(&sum));
+
+        // in plain f64 the long path costs 0 and would win
+        let edges = graph(&[(0, 1, 1e30), (1, 2, 1.0), (2, 3, -1e30), (0, 3, 0.5)]);
+        assert_eq!(positions(get_path(&edges, &node(0), &node(3))), Some(vec![0, 3]));
+    }
+}
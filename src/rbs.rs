@@ -0,0 +1,460 @@
+use aho_corasick::AhoCorasick;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// The Shine-Dalgarno classes PHANOTATE scores, after Prodigal's RBS motifs.
+///
+/// One class per line: its score, the motifs it accepts written 5' to 3' on
+/// the coding strand, and the spacers allowed between the motif and the start
+/// codon. Several lines may share a score. Blank lines and `#` comments are
+/// skipped; files given to `MotifTable::from_file` use the same layout.
+pub const DEFAULT_MOTIFS: &str = "\
+# score\tmotifs\tspacers
+27\tAGGAGG\t5-10
+26\tAGGAGG\t3-4
+25\tAGGAGG\t11-12
+24\tGGAGG\t5-10
+23\tGGAGG\t3-4
+22\tAGGAG\t5-10
+21\tAGGAG\t3-4
+20\tAGGAG,GGAGG\t11-12
+19\tAGCAGG,AGTAGG,AGAAGG,AGGCGG,AGGGGG,AGGTGG\t5-10
+18\tAGCAGG,AGTAGG,AGAAGG,AGGCGG,AGGGGG,AGGTGG\t3-4
+17\tAGCAGG,AGTAGG,AGAAGG,AGGCGG,AGGGGG,AGGTGG\t11-12
+16\tGAGG,GGAG\t5-10
+15\tAGGA\t5-10
+14\tGGTGG,GGGGG,GGCGG\t5-10
+13\tGGA,GAG,AGG\t5-10
+12\tAGGA,GGAG,GAGG\t11-12
+11\tAGGA,GGAG,GAGG\t3-4
+10\tAGGAG,GGAGG,AGGAGG\t13-15
+9\tAGAAG,AGTAG,AGCAG\t5-10
+8\tGGTGG,GGGGG,GGCGG\t3-4
+7\tGGTGG,GGGGG,GGCGG\t11-12
+6\tGGA,GAG,AGG\t11-12
+5\tAGAAG,AGTAG,AGCAG\t3-4
+4\tAGAAG,AGTAG,AGCAG\t11-12
+3\tAGGA,GGAG,GAGG\t13-15
+2\tGGA,GAG,AGG\t13-15
+2\tAGAAGG,AGTAGG,AGCAGG\t13-15
+2\tGGTGG,GGGGG,GGCGG\t13-15
+1\tGGA,GAG,AGG\t3-4
+";
+
+/// One Shine-Dalgarno class: any of `motifs` with one of `spacers` bases
+/// between it and the start codon.
+#[derive(Clone, Debug)]
+pub struct RbsClass {
+    pub score: u32,
+    pub motifs: Vec<String>,
+    pub spacers: Vec<RangeInclusive<usize>>,
+}
+
+/// The best class found upstream of a start codon.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RbsMatch {
+    pub score: u32,
+    pub motif: String,
+    pub spacer: usize,
+}
+
+/// A table of Shine-Dalgarno classes compiled into one Aho-Corasick
+/// automaton over the reversed motifs.
+pub struct MotifTable {
+    classes: Vec<RbsClass>,
+    matcher: AhoCorasick,
+    /// the classes accepting each automaton pattern
+    pattern_classes: Vec<Vec<usize>>,
+    /// each pattern written 5' to 3'
+    motifs: Vec<String>,
+}
+
+fn parse_spacers(raw: &str) -> Result<Vec<RangeInclusive<usize>>, String> {
+    raw.split(',')
+        .map(|range| {
+            let (min, max) = range.split_once('-').unwrap_or((range, range));
+            let parse = |n: &str| n.trim().parse::<usize>().map_err(|_| format!("invalid spacer `{}`", range));
+            Ok(parse(min)?..=parse(max)?)
+        })
+        .collect()
+}
+
+impl MotifTable {
+    pub fn new(classes: Vec<RbsClass>) -> Result<Self, String> {
+        let mut motifs: Vec<String> = Vec::new();
+        let mut pattern_classes: Vec<Vec<usize>> = Vec::new();
+        for (i, class) in classes.iter().enumerate() {
+            for motif in &class.motifs {
+                match motifs.iter().position(|m| m == motif) {
+                    Some(p) => pattern_classes[p].push(i),
+                    None => {
+                        motifs.push(motif.clone());
+                        pattern_classes.push(vec![i]);
+                    }
+                }
+            }
+        }
+        let reversed = motifs.iter().map(|m| m.bytes().rev().collect::<Vec<u8>>());
+        let matcher = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(reversed)
+            .map_err(|e| e.to_string())?;
+        Ok(MotifTable { classes, matcher, pattern_classes, motifs })
+    }
+
+    /// Parse a motif table laid out like `DEFAULT_MOTIFS`.
+    pub fn parse(table: &str) -> Result<Self, String> {
+        let mut classes = Vec::new();
+        for (n, line) in table.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [score, motifs, spacers] = fields[..] else {
+                return Err(format!("line {}: expected score, motifs and spacers", n + 1));
+            };
+            classes.push(RbsClass {
+                score: score.parse().map_err(|_| format!("line {}: invalid score `{}`", n + 1, score))?,
+                motifs: motifs.split(',').map(|m| m.to_ascii_uppercase()).collect(),
+                spacers: parse_spacers(spacers).map_err(|e| format!("line {}: {}", n + 1, e))?,
+            });
+        }
+        MotifTable::new(classes)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let table = fs::read_to_string(path)?;
+        MotifTable::parse(&table).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Highest score any class can give, so scores index `0..=max_score()`.
+    pub fn max_score(&self) -> u32 {
+        self.classes.iter().map(|c| c.score).max().unwrap_or(0)
+    }
+
+    /// Find the highest scoring class in the region just upstream of a start
+    /// codon, given 5' to 3' and ending right before the codon.
+    pub fn best_match(&self, upstream: &[u8]) -> Option<RbsMatch> {
+        let reversed: Vec<u8> = upstream.iter().rev().copied().collect();
+        let mut best: Option<RbsMatch> = None;
+        for found in self.matcher.find_overlapping_iter(&reversed) {
+            let pattern = found.pattern().as_usize();
+            let spacer = found.start();
+            for &c in &self.pattern_classes[pattern] {
+                let class = &self.classes[c];
+                if best.as_ref().is_some_and(|b| b.score >= class.score) {
+                    continue;
+                }
+                if class.spacers.iter().any(|range| range.contains(&spacer)) {
+                    best = Some(RbsMatch { score: class.score, motif: self.motifs[pattern].clone(), spacer });
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Log-odds, in nats, covered by each score class of a PWM or k-mer model.
+pub const SCORE_STEP: f64 = 0.5;
+/// Fraction of a PWM row's total added to every base, so no base is ruled
+/// out entirely.
+const PWM_PSEUDOCOUNT: f64 = 0.01;
+/// Longest k-mer a `KmerTable` takes: its table holds all 4^k k-mers, 128 MiB
+/// of them at this length.
+pub const MAX_KMER_K: usize = 12;
+
+fn base_index(base: u8) -> Option<usize> {
+    match base.to_ascii_uppercase() {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' | b'U' => Some(3),
+        _ => None,
+    }
+}
+
+fn kmer_index(kmer: &[u8]) -> Option<usize> {
+    kmer.iter().try_fold(0, |index, &base| Some(index * 4 + base_index(base)?))
+}
+
+/// Score class of a log-odds: 0 for none at all, then one class per
+/// `SCORE_STEP`.
+fn score_class(log_odds: f64) -> u32 {
+    (log_odds / SCORE_STEP).ceil().max(0.0) as u32
+}
+
+/// The best scoring `width`-long site upstream of a start codon, should any
+/// have a positive log-odds. Ties go to the site closest to the start codon.
+fn best_site(upstream: &[u8], width: usize, log_odds: impl Fn(&[u8]) -> Option<f64>) -> Option<RbsMatch> {
+    let mut best: Option<(usize, f64)> = None;
+    for offset in 0..(upstream.len() + 1).saturating_sub(width) {
+        let Some(score) = log_odds(&upstream[offset..offset + width]) else { continue };
+        if score > 0.0 && best.is_none_or(|(_, b)| score >= b) {
+            best = Some((offset, score));
+        }
+    }
+    best.map(|(offset, score)| RbsMatch {
+        score: score_class(score),
+        motif: String::from_utf8_lossy(&upstream[offset..offset + width]).to_ascii_uppercase(),
+        spacer: upstream.len() - offset - width,
+    })
+}
+
+/// A position weight matrix of log-odds against a uniform background, one
+/// row of A, C, G and T per motif position.
+#[derive(Clone, Debug)]
+pub struct Pwm {
+    log_odds: Vec<[f64; 4]>,
+}
+
+impl Pwm {
+    /// Build from per-position counts or frequencies of A, C, G and T.
+    pub fn from_counts(counts: &[[f64; 4]]) -> Self {
+        let log_odds = counts
+            .iter()
+            .map(|row| {
+                let total: f64 = row.iter().sum();
+                let pseudo = total * PWM_PSEUDOCOUNT;
+                row.map(|n| ((n + pseudo) / (total + 4.0 * pseudo) / 0.25).ln())
+            })
+            .collect();
+        Pwm { log_odds }
+    }
+
+    fn site_log_odds(&self, site: &[u8]) -> Option<f64> {
+        site.iter()
+            .zip(&self.log_odds)
+            .try_fold(0.0, |sum, (&base, row)| Some(sum + row[base_index(base)?]))
+    }
+
+    fn max_log_odds(&self) -> f64 {
+        self.log_odds.iter().map(|row| row.iter().cloned().fold(f64::MIN, f64::max)).sum()
+    }
+}
+
+fn check_k(k: usize) -> Result<(), String> {
+    if (1..=MAX_KMER_K).contains(&k) {
+        Ok(())
+    } else {
+        Err(format!("k-mers must be 1 to {} bases long, not {}", MAX_KMER_K, k))
+    }
+}
+
+/// Log-odds of every k-mer of a fixed length; k-mers left out are neutral.
+#[derive(Clone, Debug)]
+pub struct KmerTable {
+    k: usize,
+    log_odds: Vec<f64>,
+}
+
+impl KmerTable {
+    pub fn new(k: usize, kmers: &[(String, f64)]) -> Result<Self, String> {
+        check_k(k)?;
+        let mut log_odds = vec![0.0; 1 << (2 * k)];
+        for (kmer, score) in kmers {
+            if kmer.len() != k {
+                return Err(format!("k-mer `{}` is not {} bases long", kmer, k));
+            }
+            let index = kmer_index(kmer.as_bytes()).ok_or_else(|| format!("invalid k-mer `{}`", kmer))?;
+            log_odds[index] = *score;
+        }
+        Ok(KmerTable { k, log_odds })
+    }
+
+    /// Learn the k-mers enriched in the `foreground` upstream regions over
+    /// the `background` sequences, with a pseudocount of one.
+    pub fn learn<'s>(
+        k: usize,
+        foreground: impl IntoIterator<Item = &'s [u8]>,
+        background: impl IntoIterator<Item = &'s [u8]>,
+    ) -> Result<Self, String> {
+        check_k(k)?;
+        let count = |seqs: &mut dyn Iterator<Item = &'s [u8]>| {
+            let mut counts = vec![1.0; 1 << (2 * k)];
+            for seq in seqs {
+                for kmer in seq.windows(k) {
+                    if let Some(index) = kmer_index(kmer) {
+                        counts[index] += 1.0;
+                    }
+                }
+            }
+            let total: f64 = counts.iter().sum();
+            counts.into_iter().map(move |n| n / total)
+        };
+        let fg = count(&mut foreground.into_iter());
+        let bg = count(&mut background.into_iter());
+        Ok(KmerTable { k, log_odds: fg.zip(bg).map(|(fg, bg)| (fg / bg).ln()).collect() })
+    }
+
+    fn max_log_odds(&self) -> f64 {
+        self.log_odds.iter().cloned().fold(0.0, f64::max)
+    }
+}
+
+/// How the region upstream of a start codon is scored: Shine-Dalgarno
+/// classes, or a PWM or k-mer model for hosts that do not use them.
+pub enum RbsModel {
+    Motifs(MotifTable),
+    Pwm(Pwm),
+    Kmers(KmerTable),
+}
+
+impl RbsModel {
+    /// The built-in Shine-Dalgarno table, compiled on first use.
+    pub fn default_model() -> &'static RbsModel {
+        static MODEL: OnceLock<RbsModel> = OnceLock::new();
+        MODEL.get_or_init(|| RbsModel::Motifs(MotifTable::parse(DEFAULT_MOTIFS).unwrap()))
+    }
+
+    /// Parse a PWM or a k-mer table.
+    ///
+    /// A PWM has one line per motif position with the counts or frequencies
+    /// of A, C, G and T; a k-mer table has one k-mer and its log-odds per
+    /// line. Blank lines and `#` comments are skipped.
+    pub fn parse(model: &str) -> Result<Self, String> {
+        let mut rows: Vec<[f64; 4]> = Vec::new();
+        let mut kmers: Vec<(String, f64)> = Vec::new();
+        for (n, line) in model.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let invalid = || format!("line {}: expected four base counts or a k-mer and its log-odds", n + 1);
+            match fields[..] {
+                [a, c, g, t] if kmers.is_empty() => {
+                    let mut row = [0.0; 4];
+                    for (cell, raw) in row.iter_mut().zip([a, c, g, t]) {
+                        *cell = raw.parse().ok().filter(|&x: &f64| x >= 0.0).ok_or_else(invalid)?;
+                    }
+                    rows.push(row);
+                }
+                [kmer, score] if rows.is_empty() => {
+                    kmers.push((kmer.to_ascii_uppercase(), score.parse().map_err(|_| invalid())?));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        if !rows.is_empty() {
+            Ok(RbsModel::Pwm(Pwm::from_counts(&rows)))
+        } else if let Some((first, _)) = kmers.first() {
+            Ok(RbsModel::Kmers(KmerTable::new(first.len(), &kmers)?))
+        } else {
+            Err("empty RBS model".to_string())
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let model = fs::read_to_string(path)?;
+        RbsModel::parse(&model).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Highest score any upstream region can get, so scores index
+    /// `0..=max_score()`.
+    pub fn max_score(&self) -> u32 {
+        match self {
+            RbsModel::Motifs(table) => table.max_score(),
+            RbsModel::Pwm(pwm) => score_class(pwm.max_log_odds()),
+            RbsModel::Kmers(kmers) => score_class(kmers.max_log_odds()),
+        }
+    }
+
+    /// Find the best RBS in the region just upstream of a start codon, given
+    /// 5' to 3' and ending right before the codon.
+    pub fn best_match(&self, upstream: &[u8]) -> Option<RbsMatch> {
+        match self {
+            RbsModel::Motifs(table) => table.best_match(upstream),
+            RbsModel::Pwm(pwm) => best_site(upstream, pwm.log_odds.len(), |site| pwm.site_log_odds(site)),
+            RbsModel::Kmers(kmers) => {
+                best_site(upstream, kmers.k, |kmer| Some(kmers.log_odds[kmer_index(kmer)?]))
+            }
+        }
+    }
+
+    pub fn score(&self, upstream: &[u8]) -> u32 {
+        self.best_match(upstream).map_or(0, |m| m.score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::random_seq;
+
+    /// PHANOTATE's original `score_rbs`, kept to check `DEFAULT_MOTIFS`
+    /// against.
+    fn ladder_score(seq: &[u8]) -> u32 {
+        let s: Vec<u8> = seq.iter().rev().map(u8::to_ascii_uppercase).collect();
+        let scan = |patterns: &[&str], ranges: &[(usize, usize)]| {
+            ranges.iter().any(|&(start, end)| {
+                end <= s.len() && patterns.iter().any(|pat| &s[start..end] == pat.as_bytes())
+            })
+        };
+
+        if scan(&["GGAGGA"], &[(5,11), (6,12), (7,13), (8,14), (9,15), (10,16)]) { 27 }
+        else if scan(&["GGAGGA"], &[(3,9), (4,10)]) { 26 }
+        else if scan(&["GGAGGA"], &[(11,17), (12,18)]) { 25 }
+        else if scan(&["GGAGG"], &[(5,10), (6,11), (7,12), (8,13), (9,14), (10,15)]) { 24 }
+        else if scan(&["GGAGG"], &[(3,8), (4,9)]) { 23 }
+        else if scan(&["GAGGA"], &[(5,10), (6,11), (7,12), (8,13), (9,14), (10,15)]) { 22 }
+        else if scan(&["GAGGA"], &[(3,8), (4,9)]) { 21 }
+        else if scan(&["GAGGA", "GGAGG"], &[(11,16), (12,17)]) { 20 }
+        else if scan(&["GGACGA", "GGATGA", "GGAAGA", "GGCGGA", "GGGGGA", "GGTGGA"], &[(5,11), (6,12), (7,13), (8,14), (9,15), (10,16)]) { 19 }
+        else if scan(&["GGAAGA", "GGATGA", "GGACGA", "GGTGGA", "GGGGGA", "GGCGGA"], &[(3,9), (4,10)]) { 18 }
+        else if scan(&["GGAAGA", "GGATGA", "GGACGA", "GGTGGA", "GGGGGA", "GGCGGA"], &[(11,17), (12,18)]) { 17 }
+        else if scan(&["GGAG", "GAGG"], &[(5,9), (6,10), (7,11), (8,12), (9,13), (10,14)]) { 16 }
+        else if scan(&["AGGA"], &[(5,9), (6,10), (7,11), (8,12), (9,13), (10,14)]) { 15 }
+        else if scan(&["GGTGG", "GGGGG", "GGCGG"], &[(5,10), (6,11), (7,12), (8,13), (9,14), (10,15)]) { 14 }
+        else if scan(&["AGG", "GAG", "GGA"], &[(5,8), (6,9), (7,10), (8,11), (9,12), (10,13)]) { 13 }
+        else if scan(&["AGGA", "GAGG", "GGAG"], &[(11,15), (12,16)]) { 12 }
+        else if scan(&["AGGA", "GAGG", "GGAG"], &[(3,7), (4,8)]) { 11 }
+        else if scan(&["GAGGA", "GGAGG"], &[(13,18), (14,19), (15,20)]) || scan(&["GGAGGA"], &[(13,19), (14,20), (15,21)]) { 10 }
+        else if scan(&["GAAGA", "GATGA", "GACGA"], &[(5,10), (6,11), (7,12), (8,13), (9,14), (10,15)]) { 9 }
+        else if scan(&["GGTGG", "GGGGG", "GGCGG"], &[(3,8), (4,9)]) { 8 }
+        else if scan(&["GGTGG", "GGGGG", "GGCGG"], &[(11,16), (12,17)]) { 7 }
+        else if scan(&["AGG", "GAG", "GGA"], &[(11,14), (12,15)]) { 6 }
+        else if scan(&["GAAGA", "GATGA", "GACGA"], &[(3,8), (4,9)]) { 5 }
+        else if scan(&["GAAGA", "GATGA", "GACGA"], &[(11,16), (12,17)]) { 4 }
+        else if scan(&["AGGA", "GAGG", "GGAG"], &[(13,17), (14,18), (15,19)]) { 3 }
+        else if scan(&["AGG", "GAG", "GGA"], &[(13,16), (14,17), (15,18)])
+            || scan(&["GGAAGA", "GGATGA", "GGACGA"], &[(13,19), (14,20), (15,21)])
+            || scan(&["GGTGG", "GGGGG", "GGCGG"], &[(13,18), (14,19), (15,20)]) { 2 }
+        else if scan(&["AGG", "GAG", "GGA"], &[(3,6), (4,7)]) { 1 }
+        else { 0 }
+    }
+
+    /// Deterministic upstream regions rich in the A and G of Shine-Dalgarno
+    /// motifs, so every class turns up.
+    fn upstream_regions(count: usize, length: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| random_seq(length, i as u64, b"GGGAAACT")).collect()
+    }
+
+    #[test]
+    fn default_motifs_score_like_the_original_ladder() {
+        let model = RbsModel::default_model();
+        let mut seen = [false; 28];
+        for length in [21, 15, 6, 0] {
+            for upstream in upstream_regions(20_000, length) {
+                let expected = ladder_score(&upstream);
+                assert_eq!(model.score(&upstream), expected, "{}", String::from_utf8_lossy(&upstream));
+                assert_eq!(model.score(&upstream.to_ascii_lowercase()), expected);
+                seen[expected as usize] = true;
+            }
+        }
+        assert!(seen.iter().all(|&s| s), "classes never drawn: {:?}", seen);
+    }
+
+    #[test]
+    fn kmer_tables_reject_k_beyond_the_limit() {
+        let long = "A".repeat(MAX_KMER_K + 1);
+        assert!(KmerTable::new(MAX_KMER_K + 1, &[(long.clone(), 1.0)]).is_err());
+        assert!(KmerTable::new(0, &[]).is_err());
+        assert!(KmerTable::learn(32, [long.as_bytes()], [long.as_bytes()]).is_err());
+        assert!(RbsModel::parse(&format!("{} 1.5", "A".repeat(40))).is_err());
+        assert!(KmerTable::learn(4, [b"AGGAGG".as_slice()], [b"ACGTACGT".as_slice()]).is_ok());
+    }
+}
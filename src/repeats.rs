@@ -0,0 +1,107 @@
+use crate::seq;
+
+/// Shortest direct terminal repeat reported.
+pub const MIN_REPEAT_LEN: usize = 50;
+/// Lowest fraction of matching bases between the two copies of a repeat.
+pub const MIN_REPEAT_IDENTITY: f64 = 0.98;
+/// Exact match that anchors a candidate repeat at either end of the contig.
+const SEED_LEN: usize = 16;
+
+/// A direct terminal repeat: the first `length` bases of a contig, repeated,
+/// up to `mismatches` substitutions, as its last `length` bases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TerminalRepeat {
+    pub length: usize,
+    pub mismatches: usize,
+}
+
+impl TerminalRepeat {
+    /// 1-based inclusive bounds of the left and right copies.
+    pub fn spans(&self, contig_length: usize) -> [(usize, usize); 2] {
+        [(1, self.length), (contig_length - self.length + 1, contig_length)]
+    }
+}
+
+/// Positions where the copies differ; an ambiguous base never matches, so
+/// stretches of `N` cannot pass for a repeat.
+fn mismatches(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).filter(|(x, y)| !(seq::is_unambiguous(**x) && x.eq_ignore_ascii_case(y))).count()
+}
+
+/// Whether a seed is plain sequence, worth anchoring a repeat on.
+fn is_seed(seed: &[u8]) -> bool {
+    seed.iter().all(|&base| seq::is_unambiguous(base))
+}
+
+/// Find the longest direct terminal repeat of a contig, if any.
+///
+/// Candidates are anchored on the first `SEED_LEN` bases of the contig found
+/// again in its second half, or its last `SEED_LEN` bases found again in its
+/// first half, so a mismatch at either end of the repeat does not hide it.
+/// Seeds holding an ambiguous base anchor nothing.
+pub fn find_terminal_repeat(dna: &[u8]) -> Option<TerminalRepeat> {
+    let n = dna.len();
+    if n < 2 * MIN_REPEAT_LEN {
+        return None;
+    }
+    let half = n / 2;
+    let (head_seed, tail_seed) = (&dna[..SEED_LEN], &dna[n - SEED_LEN..]);
+
+    // lengths of the repeats the seeds would imply
+    let mut lengths: Vec<usize> = Vec::new();
+    if is_seed(head_seed) {
+        for p in n - half..=n - SEED_LEN {
+            if dna[p..p + SEED_LEN].eq_ignore_ascii_case(head_seed) {
+                lengths.push(n - p);
+            }
+        }
+    }
+    if is_seed(tail_seed) {
+        for q in 0..=half - SEED_LEN {
+            if dna[q..q + SEED_LEN].eq_ignore_ascii_case(tail_seed) {
+                lengths.push(q + SEED_LEN);
+            }
+        }
+    }
+    lengths.sort_unstable_by(|a, b| b.cmp(a));
+    lengths.dedup();
+
+    lengths
+        .into_iter()
+        .filter(|&length| length >= MIN_REPEAT_LEN)
+        .map(|length| TerminalRepeat { length, mismatches: mismatches(&dna[..length], &dna[n - length..]) })
+        .find(|repeat| (repeat.length - repeat.mismatches) as f64 >= MIN_REPEAT_IDENTITY * repeat.length as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::random_seq;
+
+    /// A stretch of ACGT too random to hold a repeat.
+    fn unique(length: usize, seed: u64) -> Vec<u8> {
+        random_seq(length, seed, b"ACGT")
+    }
+
+    #[test]
+    fn finds_a_repeat_with_mismatches() {
+        let repeat = unique(200, 1);
+        let mut right = repeat.clone();
+        right[100] = if right[100] == b'A' { b'C' } else { b'A' };
+        let dna = [repeat, unique(1000, 2), right].concat();
+        assert_eq!(find_terminal_repeat(&dna), Some(TerminalRepeat { length: 200, mismatches: 1 }));
+    }
+
+    #[test]
+    fn ignores_runs_of_n() {
+        assert_eq!(find_terminal_repeat(&[b'N'; 136]), None);
+        let dna = [vec![b'N'; 100], unique(1000, 3), vec![b'N'; 100]].concat();
+        assert_eq!(find_terminal_repeat(&dna), None);
+    }
+
+    #[test]
+    fn counts_ambiguous_bases_as_mismatches() {
+        assert_eq!(mismatches(b"ACGNT", b"acgNT"), 1);
+        assert_eq!(mismatches(b"ACGRT", b"ACGYT"), 1);
+    }
+}
@@ -1,23 +1,175 @@
-use needletail::Sequence;
+/// Shortest run of `N` treated as an assembly gap: ORFs are not extended
+/// across one, and none is found inside it.
+pub const MIN_GAP_RUN: usize = 3;
 
-pub fn revcomp_seq(seq: &[u8]) {
-    let rc = seq.reverse_complement();
-    println!("RevComp: {}", String::from_utf8_lossy(&rc));
+/// Complement of a base, IUPAC codes included and case kept; anything else,
+/// such as alignment gaps, is returned as is.
+///
+/// ```
+/// use phannotate_r::seq::complement;
+///
+/// assert_eq!(complement(b'A'), b'T');
+/// assert_eq!(complement(b'u'), b'a');
+/// assert_eq!(complement(b'R'), b'Y');
+/// assert_eq!(complement(b'b'), b'v');
+/// assert_eq!(complement(b'N'), b'N');
+/// assert_eq!(complement(b'-'), b'-');
+/// ```
+pub fn complement(base: u8) -> u8 {
+    let comp = match base.to_ascii_uppercase() {
+        b'A' => b'T',
+        b'T' | b'U' => b'A',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        b'S' | b'W' | b'N' => base.to_ascii_uppercase(),
+        _ => return base,
+    };
+    if base.is_ascii_lowercase() {
+        comp.to_ascii_lowercase()
+    } else {
+        comp
+    }
+}
+
+/// The reverse complement of `seq`, base by base, without allocating.
+///
+/// ```
+/// use phannotate_r::seq::revcomp_iter;
+///
+/// assert!(revcomp_iter(b"ATGc").eq(*b"gCAT"));
+/// ```
+pub fn revcomp_iter(seq: &[u8]) -> impl DoubleEndedIterator<Item = u8> + ExactSizeIterator + '_ {
+    seq.iter().rev().map(|&base| complement(base))
+}
+
+/// The reverse complement of `seq`.
+///
+/// ```
+/// use phannotate_r::seq::revcomp_seq;
+///
+/// assert_eq!(revcomp_seq(b"AAGGnRY"), b"RYnCCTT");
+/// ```
+pub fn revcomp_seq(seq: &[u8]) -> Vec<u8> {
+    revcomp_iter(seq).collect()
+}
+
+/// Reverse complement `seq` where it lies.
+///
+/// ```
+/// use phannotate_r::seq::revcomp_in_place;
+///
+/// let mut seq = *b"TTAcg";
+/// revcomp_in_place(&mut seq);
+/// assert_eq!(&seq, b"cgTAA");
+/// ```
+pub fn revcomp_in_place(seq: &mut [u8]) {
+    seq.reverse();
+    for base in seq.iter_mut() {
+        *base = complement(*base);
+    }
+}
+
+pub fn is_unambiguous(base: u8) -> bool {
+    matches!(base.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T')
+}
+
+/// Which bases lie in a run of at least `MIN_GAP_RUN` `N`s.
+pub fn gap_mask(dna: &[u8]) -> Vec<bool> {
+    let mut mask = vec![false; dna.len()];
+    let mut run_start = 0;
+    for i in 0..=dna.len() {
+        if i < dna.len() && dna[i].eq_ignore_ascii_case(&b'N') {
+            continue;
+        }
+        if i - run_start >= MIN_GAP_RUN {
+            mask[run_start..i].iter_mut().for_each(|m| *m = true);
+        }
+        run_start = i + 1;
+    }
+    mask
 }
 
-pub fn revcomp_base(base: &char) -> char {
-    match base.to_ascii_lowercase() {
-        'A' => 'T',
-        'T' => 'A',
-        'C' => 'G',
-        'G' => 'C',
-        _ => 'N', // fallback for ambiguous/invalid bases
+/// The bases of a contig that are not plain upper-case A, C, G or T.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BaseReport {
+    /// IUPAC codes and anything else but A, C, G and T, in either case
+    pub ambiguous: usize,
+    /// ambiguous bases in runs of `N` long enough to break ORFs
+    pub gaps: usize,
+    /// lower-case bases, ambiguous or not
+    pub soft_masked: usize,
+}
+
+pub fn base_report(dna: &[u8]) -> BaseReport {
+    BaseReport {
+        ambiguous: dna.iter().filter(|&&b| !is_unambiguous(b)).count(),
+        gaps: gap_mask(dna).iter().filter(|&&g| g).count(),
+        soft_masked: dna.iter().filter(|b| b.is_ascii_lowercase()).count(),
     }
 }
 
-pub fn revcomp_kmer(seq: &str) -> String {
-    seq.chars()
-        .rev()
-        .map(|base| revcomp_base(&base))
-        .collect()
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IUPAC: &[u8] = b"ACGTRYKMBVDHSWN";
+
+    #[test]
+    fn complement_covers_iupac() {
+        let pairs = [
+            (b'A', b'T'), (b'C', b'G'), (b'R', b'Y'), (b'K', b'M'),
+            (b'B', b'V'), (b'D', b'H'), (b'S', b'S'), (b'W', b'W'), (b'N', b'N'),
+        ];
+        for (base, comp) in pairs {
+            assert_eq!(complement(base), comp);
+            assert_eq!(complement(comp), base);
+            assert_eq!(complement(base.to_ascii_lowercase()), comp.to_ascii_lowercase());
+        }
+        assert_eq!(complement(b'U'), b'A');
+        assert_eq!(complement(b'u'), b'a');
+        for other in [b'-', b'.', b'*', b'X', b'\n'] {
+            assert_eq!(complement(other), other);
+        }
+    }
+
+    #[test]
+    fn revcomp_round_trips_and_keeps_case() {
+        let lower = IUPAC.to_ascii_lowercase();
+        let mixed: Vec<u8> = IUPAC.iter().chain(&lower).copied().collect();
+        assert_eq!(revcomp_seq(&revcomp_seq(&mixed)), mixed);
+        assert_eq!(revcomp_seq(b"AAcg"), b"cgTT");
+        assert!(revcomp_seq(b"").is_empty());
+
+        let mut in_place = mixed.clone();
+        revcomp_in_place(&mut in_place);
+        assert_eq!(in_place, revcomp_seq(&mixed));
+        assert!(revcomp_iter(&mixed).eq(in_place.iter().copied()));
+        assert_eq!(revcomp_iter(&mixed).len(), mixed.len());
+    }
+
+    #[test]
+    fn gap_mask_marks_only_long_runs_of_n() {
+        assert!(gap_mask(b"").is_empty());
+        assert_eq!(gap_mask(b"ANNA"), [false; 4]);
+        assert_eq!(gap_mask(b"NNN"), [true; 3]);
+        assert_eq!(gap_mask(b"nNnAC"), [true, true, true, false, false]);
+        assert_eq!(gap_mask(b"ACNNNN"), [false, false, true, true, true, true]);
+        assert_eq!(gap_mask(b"NNANNN"), [false, false, false, true, true, true]);
+        // other ambiguous bases do not make a gap
+        assert_eq!(gap_mask(b"NRN"), [false; 3]);
+    }
+
+    #[test]
+    fn base_report_counts_ambiguous_gap_and_masked_bases() {
+        let report = base_report(b"ACgtNNNNrA");
+        assert_eq!(report, BaseReport { ambiguous: 5, gaps: 4, soft_masked: 3 });
+    }
+}
@@ -0,0 +1,13 @@
+//! Helpers shared by the unit tests.
+
+/// A deterministic pseudo-random sequence of `len` bases drawn evenly from
+/// `alphabet`, the same for the same `seed`.
+pub fn random_seq(len: usize, seed: u64, alphabet: &[u8]) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            alphabet[(((state >> 32) * alphabet.len() as u64) >> 32) as usize]
+        })
+        .collect()
+}
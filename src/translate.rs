@@ -0,0 +1,161 @@
+/// NCBI translation tables, as published in the `gc.prt` file: amino acids
+/// and start codons indexed by codon with bases ordered TCAG.
+const TABLES: &[(u8, &[u8; 64], &[u8; 64])] = &[
+    (
+        1,
+        b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"---M------**--*----M---------------M----------------------------",
+    ),
+    (
+        4,
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"--MM------**-------M------------MMMM---------------M------------",
+    ),
+    (
+        11,
+        b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"---M------**--*----M------------MMMM---------------M------------",
+    ),
+    (
+        15,
+        b"FFLLSSSSYY*QCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"----------*---*--------------------M----------------------------",
+    ),
+    (
+        25,
+        b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        b"---M-------------------------------M---------------M------------",
+    ),
+];
+
+/// PHANOTATE's start codon weights, kept for every table that allows them.
+const START_WEIGHTS: [(&str, &str); 3] = [("ATG", "0.85"), ("GTG", "0.10"), ("TTG", "0.05")];
+
+/// Genetic codes phannotate-r can translate with.
+pub const SUPPORTED_TABLES: [u8; 5] = [1, 4, 11, 15, 25];
+
+#[derive(Clone, Copy, Debug)]
+pub struct GeneticCode {
+    pub id: u8,
+    amino_acids: &'static [u8; 64],
+    starts: &'static [u8; 64],
+}
+
+fn base_index(base: u8) -> Option<usize> {
+    match base.to_ascii_uppercase() {
+        b'T' | b'U' => Some(0),
+        b'C' => Some(1),
+        b'A' => Some(2),
+        b'G' => Some(3),
+        _ => None,
+    }
+}
+
+fn codon_index(codon: &[u8]) -> Option<usize> {
+    codon.iter().try_fold(0, |index, &base| Some(index * 4 + base_index(base)?))
+}
+
+fn codon_at(index: usize) -> String {
+    [index / 16, index / 4 % 4, index % 4]
+        .iter()
+        .map(|&i| b"TCAG"[i] as char)
+        .collect()
+}
+
+impl GeneticCode {
+    /// Look up an NCBI translation table by number.
+    pub fn new(id: u8) -> Option<Self> {
+        TABLES
+            .iter()
+            .find(|(table, _, _)| *table == id)
+            .map(|&(id, amino_acids, starts)| GeneticCode { id, amino_acids, starts })
+    }
+
+    /// Translate one codon, `X` if it contains anything but ACGT/U.
+    pub fn translate_codon(&self, codon: &[u8]) -> u8 {
+        codon_index(codon).map_or(b'X', |i| self.amino_acids[i])
+    }
+
+    pub fn is_start(&self, codon: &[u8]) -> bool {
+        codon_index(codon).is_some_and(|i| self.starts[i] == b'M')
+    }
+
+    pub fn is_stop(&self, codon: &[u8]) -> bool {
+        codon_index(codon).is_some_and(|i| self.amino_acids[i] == b'*')
+    }
+
+    /// All codons that are stops in this table, e.g. only TAA and TAG for
+    /// table 4 where TGA codes for tryptophan.
+    pub fn stop_codons(&self) -> Vec<String> {
+        (0..64)
+            .filter(|&i| self.amino_acids[i] == b'*')
+            .map(codon_at)
+            .collect()
+    }
+
+    /// Default start codons and weights in `--start-codons` syntax: the
+    /// PHANOTATE weights restricted to the starts this table allows.
+    pub fn start_codons(&self) -> String {
+        START_WEIGHTS
+            .iter()
+            .filter(|(codon, _)| self.is_start(codon.as_bytes()))
+            .map(|(codon, weight)| format!("{}:{}", codon, weight))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Translate a coding sequence that ends with a stop codon. With
+    /// `from_start`, the sequence opens on its start codon, which is read as
+    /// `M`; without, it was cut short of one and its first codon is read like
+    /// any other. The final stop is dropped and any internal stop is kept as
+    /// `*`.
+    pub fn translate(&self, seq: &[u8], from_start: bool) -> String {
+        let mut protein: Vec<u8> = seq.chunks_exact(3).map(|codon| self.translate_codon(codon)).collect();
+        if protein.last() == Some(&b'*') {
+            protein.pop();
+        }
+        if from_start && !protein.is_empty() {
+            protein[0] = b'M';
+        }
+        String::from_utf8(protein).unwrap()
+    }
+}
+
+impl Default for GeneticCode {
+    fn default() -> Self {
+        GeneticCode::new(11).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Annotator, Table};
+
+    #[test]
+    fn translate_reads_the_start_codon_as_methionine() {
+        let code = GeneticCode::new(11).unwrap();
+        assert_eq!(code.translate(b"GTGAAATAA", true), "MK");
+        // ATA is a start in table 11, but a gene cut short of its start
+        // codon keeps the amino acid it opens on
+        assert_eq!(code.translate(b"ATACAGGATTAA", false), "IQD");
+    }
+
+    /// In ATG, 20 GCT codons, `internal`, 20 GCT codons and TAA, whether
+    /// the ORFs found under `table` end on `internal` and on the TAA.
+    fn reads_through(table: u8, internal: &[u8]) -> (bool, bool) {
+        let dna = [b"ATG".as_slice(), &b"GCT".repeat(20), internal, &b"GCT".repeat(20), b"TAA"].concat();
+        let annotator = Annotator::new().min_length(60).table(Table::Fixed(table)).unwrap();
+        let orfs = annotator.annotate_contig(&dna).orfs;
+        (orfs.get_orf(1, 64).is_some(), orfs.get_orf(1, 127).is_some())
+    }
+
+    #[test]
+    fn orfs_end_on_the_stop_codons_of_the_table() {
+        assert_eq!(reads_through(11, b"TGA"), (true, false));
+        assert_eq!(reads_through(4, b"TGA"), (false, true));
+        assert_eq!(reads_through(11, b"TAG"), (true, false));
+        assert_eq!(reads_through(15, b"TAG"), (false, true));
+        assert_eq!(reads_through(15, b"TGA"), (true, false));
+    }
+}